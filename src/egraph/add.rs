@@ -154,6 +154,7 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
 
         let tmp1 = self.classes.get_mut(&id).unwrap().nodes.insert(sh.clone(), psn);
         let tmp2 = self.hashcons.insert(sh.clone(), id);
+        self.modified(id);
         if CHECKS {
             assert!(tmp1.is_none());
             assert!(tmp2.is_none());
@@ -167,6 +168,7 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
     pub(in crate::egraph) fn raw_remove_from_class(&mut self, id: Id, sh: L) -> ProvenSourceNode {
         let opt_psn = self.classes.get_mut(&id).unwrap().nodes.remove(&sh);
         let opt_id = self.hashcons.remove(&sh);
+        self.modified(id);
         if CHECKS {
             assert!(opt_psn.is_some());
             assert!(opt_id.is_some());
@@ -203,8 +205,10 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
             usages: HashSet::default(),
             syn_enode: syn_enode.clone(),
             analysis_data: N::make(&self, &syn_enode),
+            modified_at: 0,
         };
         self.classes.insert(c_id, c);
+        self.modified(c_id);
 
         { // add syn_enode to the hashcons.
             let (sh, bij) = syn_enode.weak_shape();
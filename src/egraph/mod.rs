@@ -19,6 +19,7 @@ mod analysis;
 pub use analysis::*;
 
 use std::cell::RefCell;
+use std::time::{Duration, Instant};
 
 // invariants:
 // 1. If two ENodes (that are in the EGraph) have equal .shape(), they have to be in the same eclass.
@@ -55,6 +56,19 @@ pub struct EGraph<L: Language, N: Analysis<L> = ()> {
     pub(crate) proof_registry: ProofRegistry,
 
     pub(crate) subst_method: Option<Box<dyn SubstMethod<L, N>>>,
+
+    // How many justified calls to `union_instantiations` changed the e-graph, and how much time was spent in `rebuild`.
+    // Used to compute the per-rule statistics of a Runner.
+    pub(crate) union_count: usize,
+    pub(crate) rebuild_time: Duration,
+
+    // Counts the changes to the e-graph, including new e-classes, new e-nodes in the hashcons, and any change to an e-class (union, slots, symmetries, analysis data).
+    // It never decreases, so comparing it is a cheap way to detect whether the e-graph changed.
+    modifications: usize,
+
+    // Interrupts e-matching & rewrite application, see `EGraph::set_budget`.
+    pub(crate) budget: Budget,
+    pub(crate) budget_exceeded: RefCell<Option<StopReason>>,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -86,6 +100,9 @@ pub(crate) struct EClass<L: Language, N: Analysis<L>> {
     syn_enode: L,
 
     analysis_data: N,
+
+    // The modification count of the last change to this e-class.
+    modified_at: usize,
 }
 
 
@@ -105,6 +122,11 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
             pending: Default::default(),
             proof_registry: ProofRegistry::default(),
             subst_method: Some(S::new_boxed()),
+            union_count: 0,
+            rebuild_time: Duration::ZERO,
+            modifications: 0,
+            budget: Budget::default(),
+            budget_exceeded: RefCell::new(None),
         }
     }
 
@@ -120,6 +142,26 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
         &self.classes[&self.find_id(i)].analysis_data
     }
 
+    /// Counts the modifications of this e-graph, it is incremented whenever the e-graph changes.
+    ///
+    /// This includes e-nodes & e-classes being added, unions, and changes to the analysis data computed during rebuild.
+    /// Direct changes using [EGraph::analysis_data_mut] are not counted.
+    pub fn modification_count(&self) -> usize {
+        self.modifications
+    }
+
+    pub(crate) fn modified(&mut self, i: Id) {
+        self.modifications += 1;
+        if let Some(c) = self.classes.get_mut(&i) {
+            c.modified_at = self.modifications;
+        }
+    }
+
+    // Whether the e-class `i` changed after the modification count was `count`.
+    pub(crate) fn modified_since(&self, i: Id, count: usize) -> bool {
+        self.classes[&i].modified_at > count
+    }
+
     pub fn analysis_data_mut(&mut self, i: Id) -> &mut N {
         &mut self.classes.get_mut(&self.find_id(i)).unwrap().analysis_data
     }
@@ -133,22 +175,7 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
 
     // Generates fresh slots for redundant slots.
     pub fn enodes_applied(&self, i: &AppliedId) -> HashSet<L> {
-        let mut out = HashSet::default();
-        for x in self.enodes(i.id) {
-            // This is necessary, as i.slots() might collide with the private/redundant slots of our e-nodes.
-            let set: HashSet<_> = x.all_slot_occurences()
-                                   .into_iter()
-                                   .collect::<HashSet<_>>()
-                                   .difference(&self.classes[&i.id].slots)
-                                   .copied()
-                                   .collect();
-            let x = x.refresh_slots(set);
-
-            let red = &x.slots() - &i.m.keys();
-            let fbij = SlotMap::bijection_from_fresh_to(&red);
-            let m = fbij.inverse().union(&i.m);
-            out.insert(x.apply_slotmap(&m));
-        }
+        let out: HashSet<L> = self.enodes(i.id).iter().map(|x| self.enode_applied(x, i)).collect();
 
         if CHECKS {
             for x in &out {
@@ -159,6 +186,24 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
         out
     }
 
+    // Moves the e-node `x` of the e-class `i.id` to the slots of `i`.
+    // Its private and redundant slots become fresh slots.
+    pub(crate) fn enode_applied(&self, x: &L, i: &AppliedId) -> L {
+        // This is necessary, as i.slots() might collide with the private/redundant slots of our e-nodes.
+        let set: HashSet<_> = x.all_slot_occurences()
+                               .into_iter()
+                               .collect::<HashSet<_>>()
+                               .difference(&self.classes[&i.id].slots)
+                               .copied()
+                               .collect();
+        let x = x.refresh_slots(set);
+
+        let red = &x.slots() - &i.m.keys();
+        let fbij = SlotMap::bijection_from_fresh_to(&red);
+        let m = fbij.inverse().union(&i.m);
+        x.apply_slotmap(&m)
+    }
+
     // number of enodes in the egraph.
     pub fn total_number_of_nodes(&self) -> usize {
         self.hashcons.len()
@@ -1,4 +1,5 @@
 use crate::*;
+use std::time::Instant;
 
 impl<L: Language, N: Analysis<L>> EGraph<L, N> {
     // proof.l should be i.
@@ -112,8 +113,10 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
         self.touched_class(from.id, PendingType::Full);
     }
 
+    // This doesn't check the budget, as every union has to be followed by a complete rebuild, see `Budget`.
     #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
     pub(crate) fn rebuild(&mut self) {
+        let start = Instant::now();
         if CHECKS { self.check(); }
         while let Some(sh) = self.pending.keys().cloned().next() {
             let pending_ty = self.pending.remove(&sh).unwrap();
@@ -121,6 +124,7 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
 
             if CHECKS { self.check(); }
         }
+        self.rebuild_time += start.elapsed();
     }
 
     fn handle_pending(&mut self, sh: L, pending_ty: PendingType) {
@@ -260,6 +264,7 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
 
     // upon touching an e-class, you need to update all usages of it.
     pub(crate) fn touched_class(&mut self, i: Id, pending_ty: PendingType) {
+        self.modified(i);
         for sh in &self.classes[&i].usages {
             let v = self.pending.entry(sh.clone()).or_insert(pending_ty);
             *v = v.merge(pending_ty);
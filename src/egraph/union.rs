@@ -17,15 +17,20 @@ impl<L: Language, N: Analysis<L>> EGraph<L, N> {
 
     #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
     pub fn union_instantiations(&mut self, from_pat: &Pattern<L>, to_pat: &Pattern<L>, subst: &Subst, justification: Option<String>) -> bool {
-        let a = pattern_subst(self, from_pat, subst);
-        let b = pattern_subst(self, to_pat, subst);
+        let a = pattern_subst_justified(self, from_pat, subst, justification.as_deref());
+        let b = pattern_subst_justified(self, to_pat, subst, justification.as_deref());
 
         let syn_a = self.synify_app_id(a.clone());
         let syn_b = self.synify_app_id(b.clone());
 
+        // Only the unions made on behalf of a rule are counted, see `RuleReport::unions`.
+        let justified = justification.is_some();
         let proof = ghost!(self.prove_explicit(&syn_a, &syn_b, justification));
 
         let out = self.union_internal(&a, &b, proof);
+        if out && justified {
+            self.union_count += 1;
+        }
         self.rebuild();
         out
     }
@@ -0,0 +1,65 @@
+use crate::*;
+
+/// An algorithm to extract terms from an e-graph.
+///
+/// This allows to choose the extraction algorithm, for example in an [AlgorithmSubst].
+pub trait ExtractionAlgorithm<L: Language, N: Analysis<L>> {
+    fn extract(&self, i: &AppliedId, eg: &EGraph<L, N>) -> RecExpr<L>;
+}
+
+/// The [ExtractionAlgorithm] using the [Extractor].
+///
+/// It returns a term with the minimal cost, if the cost function is monotonic.
+#[derive(Default, Clone)]
+pub struct GreedyExtraction<CF>(pub CF);
+
+impl<L: Language, N: Analysis<L>, CF: ContextCostFunction<L, N> + Clone> ExtractionAlgorithm<L, N> for GreedyExtraction<CF> {
+    fn extract(&self, i: &AppliedId, eg: &EGraph<L, N>) -> RecExpr<L> {
+        Extractor::new(eg, self.0.clone()).extract(i, eg)
+    }
+}
+
+/// The [ExtractionAlgorithm] which minimizes the DAG cost, see [DagCostFunction].
+///
+/// It starts with the terms chosen by the [DagExtractor], and improves them using [DagExtractor::improve].
+#[derive(Clone)]
+pub struct LocalSearchExtraction<CF> {
+    cost_fn: CF,
+    max_rounds: usize,
+}
+
+impl<CF> LocalSearchExtraction<CF> {
+    pub fn new(cost_fn: CF) -> Self {
+        LocalSearchExtraction { cost_fn, max_rounds: 10 }
+    }
+
+    pub fn with_max_rounds(mut self, max_rounds: usize) -> Self {
+        self.max_rounds = max_rounds;
+        self
+    }
+}
+
+impl<CF: Clone> LocalSearchExtraction<CF> {
+    /// Extracts the terms of all `roots`, minimizing their joint DAG cost.
+    ///
+    /// Returns the term of each root, with the slots given by its [AppliedId], and the shared DAG.
+    pub fn extract_many<L: Language, N: Analysis<L>>(&self, roots: &[AppliedId], eg: &EGraph<L, N>) -> (Vec<RecExpr<L>>, Dag<L>) where CF: DagCostFunction<L> {
+        let mut extractor = DagExtractor::new(eg, self.cost_fn.clone());
+        extractor.improve(roots, eg, self.max_rounds);
+        extractor.extract_shared(roots, eg)
+    }
+}
+
+impl<CF: Default> Default for LocalSearchExtraction<CF> {
+    fn default() -> Self {
+        Self::new(CF::default())
+    }
+}
+
+impl<L: Language, N: Analysis<L>, CF: DagCostFunction<L> + Clone> ExtractionAlgorithm<L, N> for LocalSearchExtraction<CF> {
+    fn extract(&self, i: &AppliedId, eg: &EGraph<L, N>) -> RecExpr<L> {
+        let mut extractor = DagExtractor::new(eg, self.cost_fn.clone());
+        extractor.improve(std::slice::from_ref(i), eg, self.max_rounds);
+        extractor.extract(i, eg).to_rec_expr()
+    }
+}
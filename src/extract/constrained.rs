@@ -0,0 +1,230 @@
+use crate::*;
+
+type Forbidden<L> = Box<dyn Fn(&L) -> bool>;
+
+/// The constraints for a [ConstrainedExtractor], built like `Constraints::new().forbid(..).with_max_depth(..)`.
+pub struct Constraints<L: Language> {
+    forbidden: Vec<Forbidden<L>>,
+    forbidden_patterns: Vec<Pattern<L>>,
+    required_patterns: Vec<Pattern<L>>,
+    max_depth: Option<usize>,
+    max_candidates: usize,
+}
+
+impl<L: Language> Default for Constraints<L> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<L: Language> Constraints<L> {
+    pub fn new() -> Self {
+        Constraints {
+            forbidden: Vec::new(),
+            forbidden_patterns: Vec::new(),
+            required_patterns: Vec::new(),
+            max_depth: None,
+            max_candidates: 10_000,
+        }
+    }
+
+    /// Forbids all e-nodes for which `f` returns true, like `|x| matches!(x, Lambda::Let(..))`.
+    pub fn forbid(mut self, f: impl Fn(&L) -> bool + 'static) -> Self {
+        self.forbidden.push(Box::new(f));
+        self
+    }
+
+    /// Forbids all terms with a subterm matching `pattern`.
+    pub fn forbid_pattern(mut self, pattern: Pattern<L>) -> Self {
+        self.forbidden_patterns.push(pattern);
+        self
+    }
+
+    /// Only allows terms with a subterm matching `pattern`.
+    pub fn require_pattern(mut self, pattern: Pattern<L>) -> Self {
+        self.required_patterns.push(pattern);
+        self
+    }
+
+    /// Only allows terms of depth at most `max_depth`. Terms without children have depth 1.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// The number of terms checked against the patterns, before the extractor gives up.
+    ///
+    /// The default is 10000.
+    pub fn with_max_candidates(mut self, max_candidates: usize) -> Self {
+        self.max_candidates = max_candidates;
+        self
+    }
+
+    fn is_forbidden(&self, l: &L) -> bool {
+        self.forbidden.iter().any(|f| f(l))
+    }
+
+    // Checks the patterns on `re`, which is added to `eg` for that.
+    // All candidates of one extraction share `eg`, so that their common subterms are only added once.
+    fn check_patterns(&self, re: &RecExpr<L>, eg: &mut EGraph<L>) -> bool {
+        if self.forbidden_patterns.is_empty() && self.required_patterns.is_empty() {
+            return true;
+        }
+
+        let mut ids = Vec::new();
+        add_subterms(re, eg, &mut ids);
+        ids.sort();
+        ids.dedup();
+        let matches = |p: &Pattern<L>| !ematch_ids(eg, p, &ids).is_empty();
+        self.forbidden_patterns.iter().all(|p| !matches(p))
+            && self.required_patterns.iter().all(matches)
+    }
+}
+
+// Adds `re` to `eg` like [EGraph::add_syn_expr], and pushes the e-classes of all of its subterms to `ids`.
+fn add_subterms<L: Language>(re: &RecExpr<L>, eg: &mut EGraph<L>, ids: &mut Vec<Id>) -> AppliedId {
+    let mut n = re.node.clone();
+    for (x, child) in n.applied_id_occurences_mut().into_iter().zip(&re.children) {
+        *x = add_subterms(child, eg, ids);
+    }
+    let i = eg.add_syn(n);
+    ids.push(i.id);
+    i
+}
+
+/// The reason why a [ConstrainedExtractor] didn't return a term.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConstraintError {
+    /// No term of the e-class satisfies the constraints.
+    Unsatisfiable,
+
+    /// The given number of terms were checked against the patterns, and none of them satisfied them.
+    /// There might still be a term satisfying all constraints, see [Constraints::with_max_candidates].
+    CandidateLimit(usize),
+}
+
+impl std::fmt::Display for ConstraintError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConstraintError::Unsatisfiable => write!(f, "no term satisfies the constraints"),
+            ConstraintError::CandidateLimit(n) => write!(f, "none of the {n} best terms satisfies the constraints"),
+        }
+    }
+}
+
+impl Error for ConstraintError {}
+
+/// An extractor that only returns terms satisfying some [Constraints].
+///
+/// Forbidden e-nodes and the maximal depth are taken into account directly, so if there is no such term, this is detected right away.
+/// The patterns are checked on the candidate terms, which are enumerated in the order of increasing cost.
+/// Hence the extracted term has the minimal cost among all terms satisfying the constraints, if the cost function is monotonic.
+pub struct ConstrainedExtractor<L: Language, CF: ContextCostFunction<L, N>, N: Analysis<L> = ()> {
+    constraints: Constraints<L>,
+    cost_fn: CF,
+
+    // `tables[d-1][i]` is the best cost of a term of `i` with depth at most `d`, without forbidden e-nodes.
+    // Without a maximal depth, only the table for unbounded depth is stored.
+    tables: Vec<HashMap<Id, CF::Cost>>,
+}
+
+impl<L: Language, CF: ContextCostFunction<L, N>, N: Analysis<L>> ConstrainedExtractor<L, CF, N> {
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
+    pub fn new(eg: &EGraph<L, N>, cost_fn: CF, constraints: Constraints<L>) -> Self {
+        if CHECKS {
+            eg.check();
+        }
+
+        let mut extractor = Self { constraints, cost_fn, tables: Vec::new() };
+        extractor.compute(eg);
+        extractor
+    }
+
+    // Computes the tables round by round, where round `d` only uses the results of round `d-1`.
+    fn compute(&mut self, eg: &EGraph<L, N>) {
+        let ids = eg.ids();
+        let nodes: Vec<(Id, L, HashSet<Slot>)> = ids.iter()
+            .flat_map(|i| eg.enodes(*i))
+            .map(|x| eg.class_nf(&x))
+            .filter(|x| !self.constraints.is_forbidden(x))
+            .map(|x| {
+                let i = eg.lookup(&x).unwrap();
+                (i.id, x, i.slots())
+            })
+            .collect();
+
+        // Without a maximal depth, we stop at the fixpoint.
+        // For a monotonic cost function, the best terms don't repeat an e-class along a path, so this takes at most `ids.len()` rounds.
+        let rounds = self.constraints.max_depth.unwrap_or(ids.len());
+
+        let mut prev: HashMap<Id, CF::Cost> = HashMap::default();
+        for _ in 0..rounds {
+            let mut next: HashMap<Id, CF::Cost> = HashMap::default();
+            for (i, x, slots) in &nodes {
+                let children = x.applied_id_occurences();
+                if !children.iter().all(|c| prev.contains_key(&c.id)) {
+                    continue;
+                }
+                let ctx = CostContext { enode: x, slots, egraph: Some(eg) };
+                let c = self.cost_fn.context_cost(&ctx, |k| prev[&children[k].id].clone());
+                if next.get(i).is_none_or(|old| c < *old) {
+                    next.insert(*i, c);
+                }
+            }
+
+            if self.constraints.max_depth.is_some() {
+                self.tables.push(prev);
+            } else if next == prev {
+                break;
+            }
+            prev = next;
+        }
+        self.tables.push(prev);
+
+        if self.constraints.max_depth.is_some() {
+            // The table for depth 0 is empty.
+            self.tables.remove(0);
+        }
+    }
+
+    // The best cost of a term of `i` with depth at most `depth`, or None if there is no such term.
+    fn bound(&self, i: Id, depth: Option<usize>) -> Option<&CF::Cost> {
+        match depth {
+            Some(0) => None,
+            Some(d) => self.tables[d - 1].get(&i),
+            None => self.tables[0].get(&i),
+        }
+    }
+
+    /// Extracts the best term of `i` that satisfies the constraints.
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
+    pub fn extract(&self, i: &AppliedId, eg: &EGraph<L, N>) -> Result<RecExpr<L>, ConstraintError> {
+        let mut search = PartialSearch::new(self, i, self.constraints.max_depth, eg);
+        let mut pattern_eg = EGraph::new();
+        while let Some((re, _)) = search.next(self, None, eg) {
+            if self.constraints.check_patterns(&re, &mut pattern_eg) {
+                return Ok(re);
+            }
+            if search.count() >= self.constraints.max_candidates {
+                return Err(ConstraintError::CandidateLimit(search.count()));
+            }
+        }
+        Err(ConstraintError::Unsatisfiable)
+    }
+}
+
+impl<L: Language, CF: ContextCostFunction<L, N>, N: Analysis<L>> HoleCosts<L, N> for ConstrainedExtractor<L, CF, N> {
+    type Cost = CF::Cost;
+
+    fn is_allowed(&self, l: &L) -> bool {
+        !self.constraints.is_forbidden(l)
+    }
+
+    fn hole_cost(&self, i: Id, depth: Option<usize>) -> Option<CF::Cost> {
+        self.bound(i, depth).cloned()
+    }
+
+    fn node_cost<C>(&self, ctx: &CostContext<'_, L, N>, costs: C) -> CF::Cost where C: Fn(usize) -> CF::Cost {
+        self.cost_fn.context_cost(ctx, costs)
+    }
+}
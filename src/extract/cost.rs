@@ -4,7 +4,7 @@ use std::marker::PhantomData;
 
 /// A cost function to guide extraction.
 ///
-/// If you want to use your e-graph analysis in your cost function, then your cost function should hold a reference to the e-graph.
+/// If your costs depend on slots or on your e-graph analysis, use a [ContextCostFunction] instead.
 pub trait CostFunction<L: Language> {
     type Cost: Ord + Clone + Debug;
     fn cost<C>(&self, enode: &L, costs: C) -> Self::Cost where C: Fn(Id) -> Self::Cost;
@@ -21,7 +21,7 @@ pub trait CostFunction<L: Language> {
 }
 
 /// The 'default' [CostFunction]. It measures the size of the abstract syntax tree of the corresponding term.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct AstSize;
 
 impl<L: Language> CostFunction<L> for AstSize {
@@ -35,3 +35,66 @@ impl<L: Language> CostFunction<L> for AstSize {
         s
     }
 }
+
+/// The e-node that a [ContextCostFunction] computes the cost of, together with its surroundings.
+pub struct CostContext<'a, L: Language, N: Analysis<L>> {
+    /// The e-node. Its children are full [AppliedId]s, so their slots are available.
+    pub enode: &'a L,
+
+    /// The free slots of the term, i.e. the slots of the e-class seen from `enode`.
+    pub slots: &'a HashSet<Slot>,
+
+    /// The e-graph, if the cost is computed during extraction.
+    /// It is `None` for [ContextCostFunction::context_cost_rec].
+    pub egraph: Option<&'a EGraph<L, N>>,
+}
+
+/// A cost function that can depend on slots, and on the e-graph and its analysis data.
+///
+/// The [Extractor] computes the cost of every e-class once, and reuses it for all uses of the e-class,
+/// no matter how they rename its slots.
+/// Hence the cost must not depend on the names of the slots, only on how they are used,
+/// e.g. on the number of free slots, or on whether a binder actually uses its slot.
+///
+/// Every [CostFunction] is also a ContextCostFunction.
+pub trait ContextCostFunction<L: Language, N: Analysis<L>> {
+    type Cost: Ord + Clone + Debug;
+
+    /// `costs(k)` is the cost of the `k`-th child, in the order of [Language::applied_id_occurences].
+    fn context_cost<C>(&self, ctx: &CostContext<'_, L, N>, costs: C) -> Self::Cost where C: Fn(usize) -> Self::Cost;
+
+    /// Computes the cost of a term outside of the e-graph, like [CostFunction::cost_rec].
+    fn context_cost_rec(&self, expr: &RecExpr<L>) -> Self::Cost {
+        rec_cost(self, expr).1
+    }
+}
+
+// Returns the free slots of `expr`, and its cost.
+fn rec_cost<L: Language, N: Analysis<L>, CF: ContextCostFunction<L, N> + ?Sized>(cost_fn: &CF, expr: &RecExpr<L>) -> (HashSet<Slot>, CF::Cost) {
+    let (child_slots, child_costs): (Vec<HashSet<Slot>>, Vec<CF::Cost>) = expr.children.iter().map(|x| rec_cost(cost_fn, x)).unzip();
+    let mut node = expr.node.clone();
+    for (i, x) in node.applied_id_occurences_mut().into_iter().enumerate() {
+        *x = AppliedId::new(Id(i), SlotMap::identity(&child_slots[i]));
+    }
+    let slots = node.slots();
+    let ctx = CostContext { enode: &node, slots: &slots, egraph: None };
+    let c = cost_fn.context_cost(&ctx, |i| child_costs[i].clone());
+    (slots, c)
+}
+
+impl<L: Language, N: Analysis<L>, CF: CostFunction<L>> ContextCostFunction<L, N> for CF {
+    type Cost = CF::Cost;
+
+    fn context_cost<C>(&self, ctx: &CostContext<'_, L, N>, costs: C) -> Self::Cost where C: Fn(usize) -> Self::Cost {
+        // The children are passed by their index, so that equal children don't get mixed up.
+        let mut node = ctx.enode.clone();
+        for (i, x) in node.applied_id_occurences_mut().into_iter().enumerate() {
+            *x = AppliedId::new(Id(i), SlotMap::new());
+        }
+        self.cost(&node, |i| costs(i.0))
+    }
+
+    fn context_cost_rec(&self, expr: &RecExpr<L>) -> Self::Cost {
+        self.cost_rec(expr)
+    }
+}
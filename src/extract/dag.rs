@@ -0,0 +1,209 @@
+use crate::*;
+
+use std::collections::BinaryHeap;
+
+/// A cost function for the [DagExtractor].
+///
+/// The cost of a term is the sum of the costs of its *distinct* subterms, so a subterm that is used twice is only paid for once.
+pub trait DagCostFunction<L: Language> {
+    /// The cost of a single e-node, excluding its children.
+    fn node_cost(&self, enode: &L) -> u64;
+}
+
+impl<L: Language> DagCostFunction<L> for AstSize {
+    fn node_cost(&self, _enode: &L) -> u64 {
+        1
+    }
+}
+
+/// A term in which equal subterms are shared, as extracted by the [DagExtractor].
+///
+/// The children of each node are earlier nodes, see [DagNode::children].
+/// Two occurences of an e-class are only shared, if they use the same slots.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dag<L: Language> {
+    pub nodes: Vec<DagNode<L>>,
+
+    /// The indices of the root nodes. With a single root, it is the last node.
+    pub roots: Vec<usize>,
+
+    /// The sum of the [DagCostFunction::node_cost] of all nodes.
+    pub cost: u64,
+}
+
+/// A node of a [Dag], whose children are given by their indices in [Dag::nodes].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DagNode<L: Language> {
+    // The child AppliedIds only give the slots passed to each child, their ids are meaningless.
+    pub(crate) enode: L,
+    pub(crate) children: Vec<usize>,
+}
+
+impl<L: Language> DagNode<L> {
+    /// The indices of the children in [Dag::nodes], in the order of [Language::applied_id_occurences].
+    pub fn children(&self) -> &[usize] {
+        &self.children
+    }
+
+    /// The e-node, where the child with index `k` is given the id `f(k)`, and the slots it uses in this node.
+    pub fn enode_with(&self, mut f: impl FnMut(usize) -> Id) -> L {
+        let mut children = self.children.iter();
+        self.enode.clone().map_applied_ids(|x| AppliedId::new(f(*children.next().unwrap()), x.m))
+    }
+}
+
+impl<L: Language> Dag<L> {
+    /// Unfolds the DAG into a tree, duplicating the shared subterms.
+    ///
+    /// If there are multiple roots, this unfolds the last root, see [Dag::to_rec_exprs].
+    pub fn to_rec_expr(&self) -> RecExpr<L> {
+        let k = self.roots.last().expect("Dag: no root to unfold!");
+        self.rec_expr_at(*k)
+    }
+
+    /// Unfolds the DAG into one tree per root.
+    pub fn to_rec_exprs(&self) -> Vec<RecExpr<L>> {
+        self.roots.iter().map(|k| self.rec_expr_at(*k)).collect()
+    }
+
+    fn rec_expr_at(&self, k: usize) -> RecExpr<L> {
+        let x = &self.nodes[k];
+        let children = x.children.iter().map(|c| self.rec_expr_at(*c)).collect();
+        RecExpr { node: x.enode.clone(), children }
+    }
+}
+
+/// An object for extracting terms with minimal DAG cost, see [DagCostFunction].
+///
+/// Finding the optimal DAG is NP-hard, so this uses a greedy heuristic:
+/// Like the [Extractor], it fixes the choice for each e-class in the order of increasing cost.
+/// But the cost of an e-node is the cost of the union of the subterms of its children, instead of their sum.
+pub struct DagExtractor<L: Language, CF: DagCostFunction<L>> {
+    // The chosen e-node for each e-class, in class normal form.
+    pub(crate) map: HashMap<Id, L>,
+    pub(crate) cost_fn: CF,
+}
+
+// The subterms of a term, represented by their e-class with slots relative to the root term, with their node cost.
+type SubtermCosts = HashMap<AppliedId, u64>;
+
+impl<L: Language, CF: DagCostFunction<L>> DagExtractor<L, CF> {
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
+    pub fn new<N: Analysis<L>>(eg: &EGraph<L, N>, cost_fn: CF) -> Self {
+        if CHECKS {
+            eg.check();
+        }
+
+        // The subterms of the chosen term of each e-class.
+        let mut subterms: HashMap<Id, SubtermCosts> = HashMap::default();
+        let mut map: HashMap<Id, L> = HashMap::default();
+        let mut queue: BinaryHeap<WithOrdRev<(L, SubtermCosts), u64>> = BinaryHeap::new();
+
+        for id in eg.ids() {
+            for x in eg.enodes(id) {
+                if x.applied_id_occurences().is_empty() {
+                    let x = eg.class_nf(&x);
+                    queue.push(Self::candidate(eg, &cost_fn, &subterms, x));
+                }
+            }
+        }
+
+        while let Some(WithOrdRev((enode, s), _)) = queue.pop() {
+            let i = eg.lookup(&enode).unwrap();
+            if map.contains_key(&i.id) {
+                continue;
+            }
+            map.insert(i.id, enode);
+            subterms.insert(i.id, s);
+
+            for x in eg.usages(i.id) {
+                if x.applied_id_occurences().iter().all(|i| map.contains_key(&i.id)) {
+                    if eg.lookup(&x).map(|i| map.contains_key(&i.id)).unwrap_or(false) {
+                        continue;
+                    }
+                    let x = eg.class_nf(&x);
+                    queue.push(Self::candidate(eg, &cost_fn, &subterms, x));
+                }
+            }
+        }
+
+        Self { map, cost_fn }
+    }
+
+    // Computes the subterms of `x`, given the subterms of its children.
+    fn candidate<N: Analysis<L>>(eg: &EGraph<L, N>, cost_fn: &CF, subterms: &HashMap<Id, SubtermCosts>, x: L) -> WithOrdRev<(L, SubtermCosts), u64> {
+        let mut s = SubtermCosts::default();
+        for child in x.applied_id_occurences() {
+            for (k, c) in &subterms[&child.id] {
+                s.insert(rename_within(k, &child.m), *c);
+            }
+        }
+        let i = eg.lookup(&x).unwrap();
+        s.insert(eg.mk_sem_identity_applied_id(i.id), cost_fn.node_cost(&x));
+
+        let cost = s.values().fold(0u64, |a, b| a.saturating_add(*b));
+        WithOrdRev((x, s), cost)
+    }
+
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
+    pub fn extract<N: Analysis<L>>(&self, i: &AppliedId, eg: &EGraph<L, N>) -> Dag<L> {
+        self.extract_dag(std::slice::from_ref(i), eg)
+    }
+
+    /// Extracts the terms of all `roots` into a single DAG, so that they share their common subterms.
+    ///
+    /// Returns the term of each root, with the slots given by its [AppliedId], and the shared DAG.
+    /// This keeps the e-nodes chosen for each e-class, which are chosen for each root on its own.
+    /// So the joint cost is not minimized, unless [DagExtractor::improve] was called beforehand,
+    /// which is what [LocalSearchExtraction::extract_many] does.
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
+    pub fn extract_shared<N: Analysis<L>>(&self, roots: &[AppliedId], eg: &EGraph<L, N>) -> (Vec<RecExpr<L>>, Dag<L>) {
+        let dag = self.extract_dag(roots, eg);
+        (dag.to_rec_exprs(), dag)
+    }
+
+    fn extract_dag<N: Analysis<L>>(&self, roots: &[AppliedId], eg: &EGraph<L, N>) -> Dag<L> {
+        let mut nodes = Vec::new();
+        let mut memo = HashMap::default();
+        let roots = roots.iter()
+            .map(|i| self.build(&eg.find_applied_id(i), false, &mut nodes, &mut memo))
+            .collect();
+
+        let cost = nodes.iter().map(|x| self.cost_fn.node_cost(&x.enode)).fold(0u64, |a, b| a.saturating_add(b));
+        Dag { nodes, roots, cost }
+    }
+
+    // Adds the term of `i` to `nodes` (if it's not there yet), and returns its index.
+    // If `refresh` is set, every node gets its own private slots.
+    pub(crate) fn build(&self, i: &AppliedId, refresh: bool, nodes: &mut Vec<DagNode<L>>, memo: &mut HashMap<AppliedId, usize>) -> usize {
+        if let Some(k) = memo.get(i) {
+            return *k;
+        }
+
+        let mut l = self.map[&i.id].apply_slotmap(&i.m);
+        if refresh {
+            l = l.refresh_private();
+        }
+        let mut children = Vec::new();
+        let enode = l.map_applied_ids(|child| {
+            children.push(self.build(&child, refresh, nodes, memo));
+            AppliedId::new(AppliedId::null().id, SlotMap::identity(&child.slots()))
+        });
+
+        nodes.push(DagNode { enode, children });
+        let k = nodes.len() - 1;
+        memo.insert(i.clone(), k);
+        k
+    }
+}
+
+// Renames the slots of `k` using `m`. The slots not covered by `m` are private slots of some subterm, and are kept.
+fn rename_within(k: &AppliedId, m: &SlotMap) -> AppliedId {
+    let mut m = m.clone();
+    for s in k.slots() {
+        if !m.contains_key(s) {
+            m.insert(s, s);
+        }
+    }
+    k.apply_slotmap(&m)
+}
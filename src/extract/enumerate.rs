@@ -0,0 +1,233 @@
+use crate::*;
+
+use std::collections::BinaryHeap;
+
+/// An iterator over the terms of an e-class, in the order of increasing cost.
+///
+/// Use `.take(k)` to get the `k` best terms, or [TermEnumerator::with_max_cost] to get all terms up to a cost bound.
+///
+/// The terms are alpha-distinct, and every binder gets its own fresh private slots.
+/// The e-graph is unfolded lazily, so cycles are fine: there might be infinitely many terms, but each of them is found after finitely many steps.
+///
+/// The [CostFunction] has to be monotonic, i.e. a term can't get cheaper by making one of its subterms more expensive.
+/// Otherwise, the terms might not come out in order.
+pub struct TermEnumerator<'a, L: Language, N: Analysis<L>, CF: CostFunction<L>> {
+    eg: &'a EGraph<L, N>,
+
+    // Used to estimate the cost of the unexpanded parts of a term.
+    extractor: Extractor<L, CF>,
+
+    search: PartialSearch<L, CF::Cost>,
+    max_cost: Option<CF::Cost>,
+}
+
+impl<'a, L: Language, N: Analysis<L>, CF: CostFunction<L>> TermEnumerator<'a, L, N, CF> {
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
+    pub fn new(i: &AppliedId, eg: &'a EGraph<L, N>, cost_fn: CF) -> Self {
+        let extractor = Extractor::new(eg, cost_fn);
+        let search = PartialSearch::new(&extractor, i, None, eg);
+        Self { eg, extractor, search, max_cost: None }
+    }
+
+    /// Stops after the last term with cost at most `max_cost`.
+    ///
+    /// If the e-graph has a cycle that doesn't increase the cost, there are infinitely many terms below any bound.
+    pub fn with_max_cost(mut self, max_cost: CF::Cost) -> Self {
+        self.max_cost = Some(max_cost);
+        self
+    }
+}
+
+impl<'a, L: Language, N: Analysis<L>, CF: CostFunction<L>> Iterator for TermEnumerator<'a, L, N, CF> {
+    type Item = (RecExpr<L>, CF::Cost);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.search.next(&self.extractor, self.max_cost.as_ref(), self.eg)
+    }
+}
+
+// What a [PartialSearch] may fill its holes with, and how much that costs at least.
+pub(crate) trait HoleCosts<L: Language, N: Analysis<L>> {
+    type Cost: Ord + Clone;
+
+    fn is_allowed(&self, l: &L) -> bool;
+
+    // The best cost of an allowed term of `i`, with depth at most `depth` if given, or None if there is no such term.
+    fn hole_cost(&self, i: Id, depth: Option<usize>) -> Option<Self::Cost>;
+
+    fn node_cost<C>(&self, ctx: &CostContext<'_, L, N>, costs: C) -> Self::Cost where C: Fn(usize) -> Self::Cost;
+}
+
+// The [TermEnumerator] allows all terms, so the best term of each e-class is a lower bound for its holes.
+impl<L: Language, N: Analysis<L>, CF: ContextCostFunction<L, N, Cost = C>, C: Ord + Clone + Debug> HoleCosts<L, N> for Extractor<L, CF, C> {
+    type Cost = C;
+
+    fn is_allowed(&self, _l: &L) -> bool {
+        true
+    }
+
+    fn hole_cost(&self, i: Id, _depth: Option<usize>) -> Option<C> {
+        self.map.get(&i).map(|x| x.1.clone())
+    }
+
+    fn node_cost<F>(&self, ctx: &CostContext<'_, L, N>, costs: F) -> C where F: Fn(usize) -> C {
+        self.cost_fn.context_cost(ctx, costs)
+    }
+}
+
+// A best-first search over the terms of an e-class, which returns them in the order of increasing cost.
+// The e-graph is unfolded lazily, by filling the first hole of the cheapest partial term.
+pub(crate) struct PartialSearch<L: Language, C: Ord> {
+    queue: BinaryHeap<WithOrdRev<Partial<L>, C>>,
+
+    // The terms returned so far, see `alpha_key`.
+    seen: HashSet<Vec<(L, Vec<bool>)>>,
+}
+
+// A term in which some subterms are still undecided.
+#[derive(Clone, PartialEq, Eq)]
+pub(crate) enum Partial<L: Language> {
+    // The remaining depth of the subterm, if there is a maximal depth.
+    Hole(AppliedId, Option<usize>),
+
+    // Also stores the free slots of the subterm.
+    Node(L, HashSet<Slot>, Vec<Partial<L>>),
+}
+
+impl<L: Language, C: Ord + Clone> PartialSearch<L, C> {
+    // Starts the search for the terms of `i` with depth at most `depth`, if given.
+    pub(crate) fn new<N: Analysis<L>>(h: &impl HoleCosts<L, N, Cost = C>, i: &AppliedId, depth: Option<usize>, eg: &EGraph<L, N>) -> Self {
+        let i = eg.find_applied_id(i);
+        let mut queue = BinaryHeap::new();
+        if let Some(c) = h.hole_cost(i.id, depth) {
+            queue.push(WithOrdRev(Partial::Hole(i, depth), c));
+        }
+        PartialSearch { queue, seen: HashSet::default() }
+    }
+
+    // The number of terms returned so far.
+    pub(crate) fn count(&self) -> usize {
+        self.seen.len()
+    }
+
+    // Returns the next term and its cost, unless it costs more than `max_cost`.
+    pub(crate) fn next<N: Analysis<L>>(&mut self, h: &impl HoleCosts<L, N, Cost = C>, max_cost: Option<&C>, eg: &EGraph<L, N>) -> Option<(RecExpr<L>, C)> {
+        while let Some(WithOrdRev(p, c)) = self.queue.pop() {
+            if max_cost.is_some_and(|m| c > *m) {
+                self.queue.clear();
+                return None;
+            }
+
+            match expand(&p, h, eg) {
+                Some(v) => {
+                    for p in v {
+                        let c = cost(&p, h, eg);
+                        self.queue.push(WithOrdRev(p, c));
+                    }
+                },
+                None => {
+                    // Different e-nodes can represent the same term, if they only differ by symmetries.
+                    let re = to_rec_expr(p);
+                    if self.seen.insert(alpha_key(&re)) {
+                        return Some((re, c));
+                    }
+                },
+            }
+        }
+        None
+    }
+}
+
+// The cost of `p`, where every hole is filled with its cheapest allowed term.
+fn cost<L: Language, N: Analysis<L>, H: HoleCosts<L, N>>(p: &Partial<L>, h: &H, eg: &EGraph<L, N>) -> H::Cost {
+    match p {
+        Partial::Hole(i, depth) => h.hole_cost(i.id, *depth).unwrap(),
+        Partial::Node(l, slots, children) => {
+            let child_costs: Vec<H::Cost> = children.iter().map(|x| cost(x, h, eg)).collect();
+            let ctx = CostContext { enode: l, slots, egraph: Some(eg) };
+            h.node_cost(&ctx, |k| child_costs[k].clone())
+        },
+    }
+}
+
+// Fills the first hole of `p` in every allowed way, or returns None if `p` has no holes.
+fn expand<L: Language, N: Analysis<L>, H: HoleCosts<L, N>>(p: &Partial<L>, h: &H, eg: &EGraph<L, N>) -> Option<Vec<Partial<L>>> {
+    match p {
+        Partial::Hole(i, depth) => {
+            // The symmetries of the e-class permute the slots of its e-nodes.
+            let variants: HashSet<AppliedId> = eg.classes[&i.id].group.all_perms().into_iter()
+                .map(|p| AppliedId::new(i.id, p.elem.compose(&i.m)))
+                .collect();
+            let child_depth = depth.map(|d| d - 1);
+            let mut out = Vec::new();
+            for l in variants.iter().flat_map(|j| eg.enodes_applied(j)) {
+                if !h.is_allowed(&l) {
+                    continue;
+                }
+                let children: Vec<Partial<L>> = l.applied_id_occurences().iter()
+                    .map(|x| Partial::Hole(eg.find_applied_id(x), child_depth))
+                    .collect();
+
+                // Skip e-nodes without any allowed term.
+                if children.iter().all(|x| matches!(x, Partial::Hole(c, d) if h.hole_cost(c.id, *d).is_some())) {
+                    out.push(Partial::Node(l, i.slots(), children));
+                }
+            }
+            Some(out)
+        },
+        Partial::Node(l, slots, children) => {
+            for (k, c) in children.iter().enumerate() {
+                if let Some(v) = expand(c, h, eg) {
+                    let out = v.into_iter().map(|c| {
+                        let mut children = children.clone();
+                        children[k] = c;
+                        Partial::Node(l.clone(), slots.clone(), children)
+                    }).collect();
+                    return Some(out);
+                }
+            }
+            None
+        },
+    }
+}
+
+fn to_rec_expr<L: Language>(p: Partial<L>) -> RecExpr<L> {
+    match p {
+        Partial::Hole(..) => panic!("PartialSearch: term still has holes!"),
+        Partial::Node(node, _, children) => RecExpr {
+            node,
+            children: children.into_iter().map(to_rec_expr).collect(),
+        },
+    }
+}
+
+// Identifies a term up to renaming of its private slots.
+// As every binder has its own private slots, it's enough to number them in the order they are bound.
+// For each slot occurence, we also store whether it is a (renamed) private slot, so they can't be confused with free slots.
+pub(crate) fn alpha_key<L: Language>(re: &RecExpr<L>) -> Vec<(L, Vec<bool>)> {
+    fn rec<L: Language>(re: &RecExpr<L>, names: &mut SlotMap, out: &mut Vec<(L, Vec<bool>)>) {
+        for s in re.node.private_slot_occurences() {
+            if !names.contains_key(s) {
+                names.insert(s, Slot::numeric(names.len() as u32));
+            }
+        }
+
+        let mut node = nullify_app_ids(&re.node);
+        let mut private = Vec::new();
+        for s in node.all_slot_occurences_mut() {
+            private.push(names.contains_key(*s));
+            if let Some(t) = names.get(*s) {
+                *s = t;
+            }
+        }
+        out.push((node, private));
+
+        for c in &re.children {
+            rec(c, names, out);
+        }
+    }
+
+    let mut out = Vec::new();
+    rec(re, &mut SlotMap::new(), &mut out);
+    out
+}
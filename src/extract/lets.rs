@@ -0,0 +1,157 @@
+use crate::*;
+
+impl<L: Language, CF: DagCostFunction<L>> DagExtractor<L, CF> {
+    /// Extracts a term, in which the subterms that are used more than once are bound by lets.
+    ///
+    /// `mk_let(x, t, b)` should build the term `let x = t in b`, binding `x` in `b`.
+    /// `mk_var(x)` should build the term that refers to the let-bound slot `x`.
+    ///
+    /// Each let is inserted at the lowest position that contains all uses of its subterm,
+    /// but stays within the binders of the free slots of the subterm.
+    /// A subterm whose uses are spread over several children of such a binder is not let-bound.
+    /// Subterms without children (like variables) are never let-bound.
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
+    pub fn extract_let<N: Analysis<L>>(
+        &self,
+        i: &AppliedId,
+        eg: &EGraph<L, N>,
+        mk_let: impl Fn(Slot, RecExpr<L>, RecExpr<L>) -> RecExpr<L>,
+        mk_var: impl Fn(Slot) -> RecExpr<L>,
+    ) -> RecExpr<L> {
+        let i = eg.find_applied_id(i);
+
+        // Every node gets its own private slots, so that the binder of a slot dominates all of its uses.
+        let mut nodes = Vec::new();
+        self.build(&i, true, &mut nodes, &mut HashMap::default());
+
+        LetBuilder::new(nodes, mk_let, mk_var).emit(None)
+    }
+}
+
+// Where a let is inserted: around a node, or around one of its children.
+type Position = (usize, Option<usize>);
+
+struct LetBuilder<L: Language, F, G> {
+    nodes: Vec<DagNode<L>>,
+
+    // The let-bound slot of each node, if it is let-bound.
+    vars: Vec<Option<Slot>>,
+
+    // The let-bound nodes to insert at each position, in ascending order.
+    lets: HashMap<Position, Vec<usize>>,
+
+    mk_let: F,
+    mk_var: G,
+}
+
+impl<L, F, G> LetBuilder<L, F, G>
+where
+    L: Language,
+    F: Fn(Slot, RecExpr<L>, RecExpr<L>) -> RecExpr<L>,
+    G: Fn(Slot) -> RecExpr<L>,
+{
+    fn new(nodes: Vec<DagNode<L>>, mk_let: F, mk_var: G) -> Self {
+        let n = nodes.len();
+        let root = n - 1;
+        let children: Vec<&[usize]> = nodes.iter().map(|x| x.children()).collect();
+
+        let mut parents: Vec<Vec<usize>> = vec![Vec::new(); n];
+        for (k, cs) in children.iter().enumerate() {
+            for c in cs.iter() {
+                parents[*c].push(k);
+            }
+        }
+
+        // Immediate dominators. Children come before their parents in `nodes`, so going backwards visits all parents first.
+        let mut idom: Vec<usize> = vec![root; n];
+        let mut depth: Vec<usize> = vec![0; n];
+        for k in (0..root).rev() {
+            let mut d = parents[k][0];
+            for p in &parents[k][1..] {
+                d = lca(d, *p, &idom, &depth);
+            }
+            idom[k] = d;
+            depth[k] = depth[d] + 1;
+        }
+
+        let mut vars: Vec<Option<Slot>> = (0..n)
+            .map(|k| (k != root && parents[k].len() > 1 && !children[k].is_empty()).then(Slot::fresh))
+            .collect();
+
+        let mut lets: HashMap<Position, Vec<usize>> = HashMap::default();
+        for k in 0..n {
+            if vars[k].is_none() {
+                continue;
+            }
+
+            let d = idom[k];
+            let free = nodes[k].enode.slots();
+            if !nodes[d].enode.private_slots().iter().any(|s| free.contains(s)) {
+                lets.entry((d, None)).or_default().push(k);
+                continue;
+            }
+
+            // `d` binds a free slot of `k`, so the let has to go below the binder.
+            // If only one child of `d` reaches `k`, the let goes around that child.
+            // Otherwise no single let below the binder reaches all uses of `k`, so `k` is not let-bound.
+            let positions: Vec<usize> = (0..children[d].len()).filter(|pos| reaches(children[d][*pos], k, &children)).collect();
+            match positions[..] {
+                [pos] => lets.entry((d, Some(pos))).or_default().push(k),
+                _ => vars[k] = None,
+            }
+        }
+
+        Self { nodes, vars, lets, mk_let, mk_var }
+    }
+
+    // Builds the term of node `k` (the root, if `None`), including the lets inserted into it.
+    fn emit(&self, k: Option<usize>) -> RecExpr<L> {
+        let k = k.unwrap_or(self.nodes.len() - 1);
+        let children = self.nodes[k].children.iter().enumerate().map(|(pos, c)| {
+            let e = match self.vars[*c] {
+                Some(x) => (self.mk_var)(x),
+                None => self.emit(Some(*c)),
+            };
+            self.wrap((k, Some(pos)), e)
+        }).collect();
+        let e = RecExpr { node: self.nodes[k].enode.clone(), children };
+        self.wrap((k, None), e)
+    }
+
+    // Wraps `e` in the lets at `position`, such that the let of the smallest node is outermost.
+    fn wrap(&self, position: Position, mut e: RecExpr<L>) -> RecExpr<L> {
+        if let Some(ks) = self.lets.get(&position) {
+            for k in ks.iter().rev() {
+                e = (self.mk_let)(self.vars[*k].unwrap(), self.emit(Some(*k)), e);
+            }
+        }
+        e
+    }
+}
+
+fn lca(mut a: usize, mut b: usize, idom: &[usize], depth: &[usize]) -> usize {
+    while a != b {
+        if depth[a] >= depth[b] {
+            a = idom[a];
+        } else {
+            b = idom[b];
+        }
+    }
+    a
+}
+
+// Whether `k` is reachable from `start`.
+fn reaches(start: usize, k: usize, children: &[&[usize]]) -> bool {
+    let mut stack = vec![start];
+    let mut seen = HashSet::default();
+    while let Some(x) = stack.pop() {
+        if x == k {
+            return true;
+        }
+        // Children have smaller indices, so nodes below `k` can't reach it.
+        if x > k && seen.insert(x) {
+            stack.extend(children[x]);
+        }
+    }
+    false
+}
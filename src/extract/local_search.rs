@@ -0,0 +1,80 @@
+use crate::*;
+
+impl<L: Language, CF: DagCostFunction<L>> DagExtractor<L, CF> {
+    /// Improves the joint DAG cost of the `roots` by local search.
+    ///
+    /// In each round, it tries to replace the chosen e-node of each e-class used by the `roots` by another e-node of that e-class,
+    /// and keeps the replacement if it lowers the cost.
+    /// It stops after `max_rounds` rounds, or once a round didn't find any improvement.
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
+    pub fn improve<N: Analysis<L>>(&mut self, roots: &[AppliedId], eg: &EGraph<L, N>, max_rounds: usize) {
+        let roots: Vec<AppliedId> = roots.iter().map(|x| eg.find_applied_id(x)).collect();
+        let mut best = self.joint_cost(&roots);
+
+        for _ in 0..max_rounds {
+            let mut improved = false;
+            for c in self.used_classes(&roots) {
+                for x in eg.enodes(c) {
+                    let x = eg.class_nf(&x);
+
+                    // `class_nf` refreshes the private slots, so the chosen e-node is compared up to renaming them:
+                    // the weak shapes agree, and so do the public slots they are applied to.
+                    if x.weak_shape() == self.map[&c].weak_shape() { continue; }
+
+                    // The new e-node can't use e-classes without a term, and it can't make the choices cyclic.
+                    let children: Vec<Id> = x.applied_id_occurences().iter().map(|i| i.id).collect();
+                    if !children.iter().all(|i| self.map.contains_key(i)) { continue; }
+                    if self.reaches(&children, c) { continue; }
+
+                    let old = self.map.insert(c, x).unwrap();
+                    let cost = self.joint_cost(&roots);
+                    if cost < best {
+                        best = cost;
+                        improved = true;
+                    } else {
+                        self.map.insert(c, old);
+                    }
+                }
+            }
+
+            if !improved { break; }
+        }
+    }
+
+    // The cost of the DAG containing the terms of all `roots`.
+    pub(crate) fn joint_cost(&self, roots: &[AppliedId]) -> u64 {
+        let mut nodes = Vec::new();
+        let mut memo = HashMap::default();
+        for r in roots {
+            self.build(r, false, &mut nodes, &mut memo);
+        }
+        nodes.iter().map(|x| self.cost_fn.node_cost(&x.enode)).fold(0u64, |a, b| a.saturating_add(b))
+    }
+
+    // The e-classes used by the chosen terms of `roots`, in a deterministic order.
+    fn used_classes(&self, roots: &[AppliedId]) -> Vec<Id> {
+        let mut seen: HashSet<Id> = HashSet::default();
+        let mut stack: Vec<Id> = roots.iter().map(|x| x.id).collect();
+        while let Some(i) = stack.pop() {
+            if seen.insert(i) {
+                stack.extend(self.map[&i].applied_id_occurences().iter().map(|x| x.id));
+            }
+        }
+        let mut out: Vec<Id> = seen.into_iter().collect();
+        out.sort();
+        out
+    }
+
+    // Whether `target` is used by the chosen terms of `start`.
+    fn reaches(&self, start: &[Id], target: Id) -> bool {
+        let mut seen: HashSet<Id> = HashSet::default();
+        let mut stack: Vec<Id> = start.to_vec();
+        while let Some(i) = stack.pop() {
+            if i == target { return true; }
+            if seen.insert(i) {
+                stack.extend(self.map[&i].applied_id_occurences().iter().map(|x| x.id));
+            }
+        }
+        false
+    }
+}
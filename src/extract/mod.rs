@@ -6,36 +6,110 @@ pub use cost::*;
 mod with_ord;
 pub use with_ord::*;
 
+mod dag;
+pub use dag::*;
+
+mod lets;
+pub use lets::*;
+
+mod enumerate;
+pub use enumerate::*;
+
+mod local_search;
+pub use local_search::*;
+
+mod algorithm;
+pub use algorithm::*;
+
+mod constrained;
+pub use constrained::*;
+
+#[cfg(feature = "sample")]
+mod sample;
+#[cfg(feature = "sample")]
+pub use sample::*;
+
 use std::collections::BinaryHeap;
 
-/// An object used for quickly extracting terms (i.e. [RecExpr]s) using a given [CostFunction].
+/// An object used for quickly extracting terms (i.e. [RecExpr]s) using a given [CostFunction] or [ContextCostFunction].
 ///
 /// Creating an Extractor will setup an extraction-table which then allows you to extract terms from many e-classes efficiently.
 /// It is most useful when doing "bulk" extractions for many classes.
-pub struct Extractor<L: Language, CF: CostFunction<L>> {
-    pub(crate) map: HashMap<Id, WithOrdRev<L, CF::Cost>>,
+///
+/// `C` is the cost type of `CF`. It only has to be given explicitly for a [ContextCostFunction] that is not a [CostFunction].
+pub struct Extractor<L: Language, CF, C: Ord = <CF as CostFunction<L>>::Cost> {
+    pub(crate) map: HashMap<Id, WithOrdRev<L, C>>,
+    cost_fn: CF,
+
+    // The modification count of the e-graph when `map` was last computed.
+    modification_count: usize,
 }
 
-impl<L: Language, CF: CostFunction<L>> Extractor<L, CF> {
+impl<L: Language, CF, C: Ord + Clone + Debug> Extractor<L, CF, C> {
     #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
-    pub fn new<N: Analysis<L>>(eg: &EGraph<L, N>, cost_fn: CF) -> Self {
+    pub fn new<N: Analysis<L>>(eg: &EGraph<L, N>, cost_fn: CF) -> Self where CF: ContextCostFunction<L, N, Cost = C> {
         if CHECKS {
             eg.check();
         }
 
+        let mut extractor = Self {
+            map: HashMap::default(),
+            cost_fn,
+            modification_count: eg.modification_count(),
+        };
+        extractor.compute(eg, &eg.ids());
+        extractor
+    }
+
+    /// Updates the extractor to the current state of `eg`, which has to be the e-graph it was created with.
+    ///
+    /// Only the e-classes that changed since the last update, and the e-classes that (transitively) use them, are recomputed.
+    /// The result is the same as creating a new extractor with [Extractor::new].
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
+    pub fn refresh<N: Analysis<L>>(&mut self, eg: &EGraph<L, N>) where CF: ContextCostFunction<L, N, Cost = C> {
+        let ids = eg.ids();
+
+        // The changed e-classes, including the new ones.
+        let mut dirty: HashSet<Id> = ids.iter()
+            .copied()
+            .filter(|i| !self.map.contains_key(i) || eg.modified_since(*i, self.modification_count))
+            .collect();
+
+        // Their costs might affect the costs of all e-classes using them.
+        let mut stack: Vec<Id> = dirty.iter().copied().collect();
+        while let Some(i) = stack.pop() {
+            for x in eg.usages(i) {
+                if let Some(j) = eg.lookup(&x) {
+                    if dirty.insert(j.id) {
+                        stack.push(j.id);
+                    }
+                }
+            }
+        }
+
+        // This also drops the e-classes that were merged into others.
+        let alive: HashSet<Id> = ids.into_iter().collect();
+        self.map.retain(|i, _| alive.contains(i) && !dirty.contains(i));
+
+        self.modification_count = eg.modification_count();
+        let dirty: Vec<Id> = dirty.into_iter().collect();
+        self.compute(eg, &dirty);
+    }
+
+    // Computes the best e-nodes of the e-classes `ids`, which are missing in `map`.
+    // All other e-classes in `map` have to be up-to-date.
+    fn compute<N: Analysis<L>>(&mut self, eg: &EGraph<L, N>, ids: &[Id]) where CF: ContextCostFunction<L, N, Cost = C> {
         // all the L in `map` and `queue` have to be
         // - in "normal-form", i.e. calling lookup on them yields an identity AppliedId.
         // - every internal slot needs to be refreshed.
 
-        // maps eclass id to their optimal RecExpr.
-        let mut map: HashMap<Id, WithOrdRev<L, CF::Cost>> = HashMap::default();
-        let mut queue: BinaryHeap<WithOrdRev<L, CF::Cost>> = BinaryHeap::new();
+        let mut queue: BinaryHeap<WithOrdRev<L, C>> = BinaryHeap::new();
 
-        for id in eg.ids() {
-            for x in eg.enodes(id) {
-                if x.applied_id_occurences().is_empty() {
-                    let x = eg.class_nf(&x);
-                    let c = cost_fn.cost(&x, |_| panic!());
+        for id in ids {
+            for x in eg.enodes(*id) {
+                let x = eg.class_nf(&x);
+                if x.applied_id_occurences().iter().all(|i| self.map.contains_key(&i.id)) {
+                    let c = Self::enode_cost(eg, &self.cost_fn, &self.map, &x);
                     queue.push(WithOrdRev(x, c));
                 }
             }
@@ -43,47 +117,88 @@ impl<L: Language, CF: CostFunction<L>> Extractor<L, CF> {
 
         while let Some(WithOrdRev(enode, c)) = queue.pop() {
             let i = eg.lookup(&enode).unwrap();
-            if map.contains_key(&i.id) {
+            if self.map.contains_key(&i.id) {
                 continue;
             }
-            map.insert(i.id, WithOrdRev(enode, c));
+            self.map.insert(i.id, WithOrdRev(enode, c));
 
             for x in eg.usages(i.id).clone() {
-                if x.applied_id_occurences().iter().all(|i| map.contains_key(&i.id)) {
-                    if eg.lookup(&x).map(|i| map.contains_key(&i.id)).unwrap_or(false) {
+                if x.applied_id_occurences().iter().all(|i| self.map.contains_key(&i.id)) {
+                    if eg.lookup(&x).map(|i| self.map.contains_key(&i.id)).unwrap_or(false) {
                         continue;
                     }
                     let x = eg.class_nf(&x);
-                    let c = cost_fn.cost(&x, |i| map[&i].1.clone());
+                    let c = Self::enode_cost(eg, &self.cost_fn, &self.map, &x);
                     queue.push(WithOrdRev(x, c));
                 }
             }
         }
+    }
 
-        Self { map }
+    // The cost of `x`, given the costs of its children in `map`.
+    fn enode_cost<N: Analysis<L>>(eg: &EGraph<L, N>, cost_fn: &CF, map: &HashMap<Id, WithOrdRev<L, C>>, x: &L) -> C where CF: ContextCostFunction<L, N, Cost = C> {
+        let children = x.applied_id_occurences();
+        let slots = eg.lookup(x).unwrap().slots();
+        let ctx = CostContext { enode: x, slots: &slots, egraph: Some(eg) };
+        cost_fn.context_cost(&ctx, |k| map[&children[k].id].1.clone())
     }
 
+    /// Extracts the best term of `i`.
+    ///
+    /// Panics if there is no term, see [Extractor::try_extract].
     #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
     pub fn extract<N: Analysis<L>>(&self, i: &AppliedId, eg: &EGraph<L, N>) -> RecExpr<L> {
+        match self.try_extract(i, eg) {
+            Some(re) => re,
+            None => panic!("Extractor: e-class {:?} has no extractable term!", eg.find_id(i.id)),
+        }
+    }
+
+    /// Extracts the best term of `i`, or returns `None` if the extractor has no term for it.
+    ///
+    /// This happens for e-classes without any finite term, and for e-classes that were added after the extractor was created.
+    /// See [Extractor::unextractable_classes].
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
+    pub fn try_extract<N: Analysis<L>>(&self, i: &AppliedId, eg: &EGraph<L, N>) -> Option<RecExpr<L>> {
         let i = eg.find_applied_id(i);
 
         let mut children = Vec::new();
 
         // do I need to refresh some slots here?
-        let l = self.map[&i.id].0.apply_slotmap(&i.m);
+        let l = self.map.get(&i.id)?.0.apply_slotmap(&i.m);
         for child in l.applied_id_occurences() {
-            let n = self.extract(&child, eg);
+            let n = self.try_extract(&child, eg)?;
             children.push(n);
         }
 
-        RecExpr {
+        Some(RecExpr {
             node: l,
             children,
+        })
+    }
+
+    /// The cost of the best term of `i`.
+    ///
+    /// Panics if there is no term, see [Extractor::try_best_cost].
+    pub fn get_best_cost<N: Analysis<L>>(&self, i: &AppliedId, eg: &EGraph<L, N>) -> C {
+        match self.try_best_cost(i, eg) {
+            Some(c) => c,
+            None => panic!("Extractor: e-class {:?} has no extractable term!", eg.find_id(i.id)),
         }
     }
 
-    pub fn get_best_cost<N: Analysis<L>>(&self, i: &AppliedId, eg: &EGraph<L, N>) -> CF::Cost {
-        self.map[&i.id].1.clone()
+    /// The cost of the best term of `i`, or `None` if the extractor has no term for it.
+    pub fn try_best_cost<N: Analysis<L>>(&self, i: &AppliedId, eg: &EGraph<L, N>) -> Option<C> {
+        let i = eg.find_id(i.id);
+        self.map.get(&i).map(|x| x.1.clone())
+    }
+
+    /// The e-classes of `eg` that this extractor has no term for.
+    ///
+    /// These are the e-classes which can't be reached from any e-node without children, i.e. which only have infinite terms.
+    /// If the e-graph changed since the extractor was created, new e-classes are listed as well.
+    pub fn unextractable_classes<N: Analysis<L>>(&self, eg: &EGraph<L, N>) -> Vec<Id> {
+        eg.ids().into_iter().filter(|i| !self.map.contains_key(i)).collect()
     }
 }
 
@@ -0,0 +1,112 @@
+use crate::*;
+
+use rand::Rng;
+
+type Weight<L> = Box<dyn Fn(&L) -> f64>;
+
+/// Samples random terms of e-classes, up to a maximal depth. Terms without children have depth 1.
+///
+/// By default, every term of depth at most `max_depth` is equally likely, where the symmetric variants of an e-node count as one term.
+/// Using [TermSampler::weighted], the probability of a term is proportional to the product of the weights of its nodes.
+///
+/// Every binder gets its own fresh private slots, as in [EGraph::enodes_applied].
+/// A sampler with the same seeded [Rng] samples the same terms, up to the names of these fresh slots.
+/// Two samplers for equal, but differently built e-graphs might order the e-nodes differently, and then sample other terms.
+///
+/// This requires the `sample` feature.
+pub struct TermSampler<'a, L: Language, N: Analysis<L>> {
+    eg: &'a EGraph<L, N>,
+    weight: Weight<L>,
+
+    // The e-nodes of each e-class, in the arbitrary order of the e-graph, which stays fixed for this sampler.
+    nodes: HashMap<Id, Vec<L>>,
+
+    // `tables[d-1][i]` is the logarithm of the total weight of the terms of `i` with depth at most `d`.
+    // Logarithms are used, as the number of terms grows exponentially with the depth.
+    tables: Vec<HashMap<Id, f64>>,
+}
+
+impl<'a, L: Language, N: Analysis<L>> TermSampler<'a, L, N> {
+    /// Samples the terms uniformly.
+    pub fn new(eg: &'a EGraph<L, N>, max_depth: usize) -> Self {
+        Self::weighted(eg, max_depth, |_| 1.0)
+    }
+
+    /// Samples the terms with probability proportional to the product of the weights of their nodes.
+    ///
+    /// The weights have to be non-negative. E-nodes with weight zero are never used.
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
+    pub fn weighted(eg: &'a EGraph<L, N>, max_depth: usize, weight: impl Fn(&L) -> f64 + 'static) -> Self {
+        let nodes: HashMap<Id, Vec<L>> = eg.ids().into_iter()
+            .map(|i| (i, eg.enodes(i).into_iter().collect()))
+            .collect();
+        let mut sampler = Self { eg, weight: Box::new(weight), nodes, tables: Vec::new() };
+
+        for d in 1..=max_depth {
+            let mut table = HashMap::default();
+            for (i, xs) in &sampler.nodes {
+                let ws: Vec<f64> = xs.iter().filter_map(|x| sampler.log_weight(x, d - 1)).collect();
+                if !ws.is_empty() {
+                    table.insert(*i, log_sum_exp(&ws));
+                }
+            }
+            sampler.tables.push(table);
+        }
+        sampler
+    }
+
+    // The logarithm of the total weight of the terms with root `x`, and depth at most `depth+1`.
+    fn log_weight(&self, x: &L, depth: usize) -> Option<f64> {
+        let w = (self.weight)(x);
+        assert!(w >= 0.0, "TermSampler: negative weight {w} for {x:?}!");
+        if w == 0.0 {
+            return None;
+        }
+
+        let mut out = w.ln();
+        for c in x.applied_id_occurences() {
+            let c = self.eg.find_id(c.id);
+            out += self.tables.get(depth.checked_sub(1)?)?.get(&c)?;
+        }
+        Some(out)
+    }
+
+    /// Samples a term of `i`, or returns `None` if `i` has no term within the maximal depth.
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
+    pub fn sample<R: Rng>(&self, i: &AppliedId, rng: &mut R) -> Option<RecExpr<L>> {
+        let i = self.eg.find_applied_id(i);
+        self.sample_rec(&i, self.tables.len(), rng)
+    }
+
+    fn sample_rec<R: Rng>(&self, i: &AppliedId, depth: usize, rng: &mut R) -> Option<RecExpr<L>> {
+        let total = *self.tables.get(depth.checked_sub(1)?)?.get(&i.id)?;
+
+        let xs = &self.nodes[&i.id];
+        let mut r: f64 = rng.gen();
+        let mut chosen = None;
+        for x in xs {
+            if let Some(w) = self.log_weight(x, depth - 1) {
+                chosen = Some(x);
+                r -= (w - total).exp();
+                if r < 0.0 {
+                    break;
+                }
+            }
+        }
+
+        // Only the chosen e-node gets fresh slots.
+        let l = self.eg.enode_applied(chosen?, i);
+        let mut children = Vec::new();
+        for c in l.applied_id_occurences() {
+            let c = self.eg.find_applied_id(&c);
+            children.push(self.sample_rec(&c, depth - 1, rng)?);
+        }
+        Some(RecExpr { node: l, children })
+    }
+}
+
+// Computes `ln(sum(exp(x)))` for all `x` in `xs`, without overflowing.
+fn log_sum_exp(xs: &[f64]) -> f64 {
+    let m = xs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    m + xs.iter().map(|x| (x - m).exp()).sum::<f64>().ln()
+}
@@ -34,6 +34,7 @@ pub use types::*;
 
 mod parse;
 pub(crate) use parse::*;
+pub use parse::ParseError;
 
 mod lang;
 pub use lang::*;
@@ -3,13 +3,34 @@ use crate::*;
 #[derive(Debug)]
 pub enum ParseError {
     TokenState(String),
-    ParseState(Vec<Token>),
-    RemainingRest(Vec<Token>),
+    ParseState(String),
+    RemainingRest(String),
     FromOpFailed(String, Vec<Child>),
-    ExpectedColonEquals(Vec<Token>),
-    ExpectedRBracket(Vec<Token>)
+    ExpectedColonEquals(String),
+    ExpectedRBracket(String),
+    ExpectedRBrace(String),
+    ExpectedSlot(String),
+    UnexpectedEnd,
 }
 
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::TokenState(s) => write!(f, "invalid token at `{s}`"),
+            ParseError::ParseState(tok) => write!(f, "unexpected tokens {tok}"),
+            ParseError::RemainingRest(tok) => write!(f, "unexpected trailing tokens {tok}"),
+            ParseError::FromOpFailed(op, children) => write!(f, "unknown operator `{op}` with {} children", children.len()),
+            ParseError::ExpectedColonEquals(tok) => write!(f, "expected `:=`, found {tok}"),
+            ParseError::ExpectedRBracket(tok) => write!(f, "expected `]`, found {tok}"),
+            ParseError::ExpectedRBrace(tok) => write!(f, "expected `}}`, found {tok}"),
+            ParseError::ExpectedSlot(tok) => write!(f, "expected a slot, found {tok}"),
+            ParseError::UnexpectedEnd => write!(f, "unexpected end of input"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
 #[derive(Debug, Clone)]
 enum Token {
     Slot(Slot), // s42
@@ -20,11 +41,16 @@ enum Token {
     RParen, // )
     LBracket, // [
     RBracket, // ]
+    LBrace, // {
+    RBrace, // }
+    Comma, // ,
+    Backslash, // \
+    DotDot, // ..
 }
 
 fn ident_char(c: char) -> bool {
     if c.is_whitespace() { return false; }
-    if "()[]".contains(c) { return false; }
+    if "()[]{},\\".contains(c) { return false; }
     true
 }
 
@@ -60,6 +86,21 @@ fn tokenize(mut s: &str) -> Result<Vec<Token>, ParseError> {
         } else if s.starts_with(']') {
             tokens.push(Token::RBracket);
             s = &s[1..];
+        } else if s.starts_with('{') {
+            tokens.push(Token::LBrace);
+            s = &s[1..];
+        } else if s.starts_with('}') {
+            tokens.push(Token::RBrace);
+            s = &s[1..];
+        } else if s.starts_with(',') {
+            tokens.push(Token::Comma);
+            s = &s[1..];
+        } else if s.starts_with('\\') {
+            tokens.push(Token::Backslash);
+            s = &s[1..];
+        } else if s.starts_with("..") {
+            tokens.push(Token::DotDot);
+            s = &s[2..];
         } else if s.starts_with(":=") {
             tokens.push(Token::ColonEquals);
             s = &s[2..];
@@ -88,7 +129,7 @@ impl<L: Language> Pattern<L> {
         let (re, rest) = parse_pattern(&tok)?;
 
         if !rest.is_empty() {
-            return Err(ParseError::RemainingRest(to_vec(rest)));
+            return Err(ParseError::RemainingRest(render(rest)));
         }
 
         Ok(re)
@@ -106,38 +147,56 @@ fn parse_pattern<L: Language>(tok: &[Token]) -> Result<(Pattern<L>, &[Token]), P
     let (mut pat, mut tok) = parse_pattern_nosubst(tok)?;
     while let Some(Token::LBracket) = tok.get(0) {
         tok = &tok[1..];
-        let (l, tok2) = parse_pattern(tok)?;
-        tok = tok2;
 
-        let Token::ColonEquals = &tok[0] else { return Err(ParseError::ExpectedColonEquals(to_vec(tok))) };
-        tok = &tok[1..];
+        // `b[x := t, y := u]` substitutes simultaneously.
+        let mut pairs = Vec::new();
+        loop {
+            let (l, tok2) = parse_pattern(tok)?;
+            tok = tok2;
+
+            let Some(Token::ColonEquals) = tok.first() else { return Err(ParseError::ExpectedColonEquals(render(tok))) };
+            tok = &tok[1..];
 
-        let (r, tok2) = parse_pattern(tok)?;
-        tok = tok2;
+            let (r, tok2) = parse_pattern(tok)?;
+            tok = tok2;
+            pairs.push((l, r));
 
-        let Token::RBracket = &tok[0] else { return Err(ParseError::ExpectedRBracket(to_vec(tok))) };
+            match tok.first() {
+                Some(Token::Comma) => tok = &tok[1..],
+                Some(Token::RBracket) => break,
+                _ => return Err(ParseError::ExpectedRBracket(render(tok))),
+            }
+        }
         tok = &tok[1..];
 
-        pat = Pattern::Subst(Box::new(pat), Box::new(l), Box::new(r));
+        pat = match <[_; 1]>::try_from(pairs) {
+            Ok([(x, t)]) => Pattern::Subst(Box::new(pat), Box::new(x), Box::new(t)),
+            Err(pairs) => Pattern::SubstMany(Box::new(pat), pairs),
+        };
     }
     Ok((pat, tok))
 }
 
 fn parse_pattern_nosubst<L: Language>(mut tok: &[Token]) -> Result<(Pattern<L>, &[Token]), ParseError> {
-    if let Token::PVar(p) = &tok[0] {
-        let pat = Pattern::PVar(p.to_string());
-        return Ok((pat, &tok[1..]));
+    let Some(first) = tok.first() else { return Err(ParseError::UnexpectedEnd) };
+
+    if let Token::PVar(p) = first {
+        return parse_pvar(p, &tok[1..]);
     }
 
-    if let Token::LParen = tok[0] {
+    if let Token::LParen = first {
         tok = &tok[1..];
 
-        let Token::Ident(op) = &tok[0] else { panic!("1"); return Err(ParseError::ParseState(to_vec(tok))) };
+        let Some(Token::Ident(op)) = tok.first() else { return Err(ParseError::ParseState(render(tok))) };
         tok = &tok[1..];
 
         let mut children = Vec::new();
         loop {
-            if let Token::RParen = tok[0] { break };
+            match tok.first() {
+                Some(Token::RParen) => break,
+                None => return Err(ParseError::UnexpectedEnd),
+                _ => {},
+            }
 
             let (child, tok2) = parse_child(tok)?;
             tok = tok2;
@@ -159,7 +218,7 @@ fn parse_pattern_nosubst<L: Language>(mut tok: &[Token]) -> Result<(Pattern<L>,
         let re = Pattern::ENode(node, children);
         Ok((re, tok))
     } else {
-        let Token::Ident(op) = &tok[0] else { panic!("2"); return Err(ParseError::ParseState(to_vec(tok))) };
+        let Token::Ident(op) = first else { return Err(ParseError::ParseState(render(tok))) };
         tok = &tok[1..];
 
         let node = L::from_op(op, vec![]).ok_or_else(|| ParseError::FromOpFailed(op.to_string(), vec![]))?;
@@ -168,13 +227,84 @@ fn parse_pattern_nosubst<L: Language>(mut tok: &[Token]) -> Result<(Pattern<L>,
     }
 }
 
+// parses the optional slot condition or slot arguments after a pattern-variable `?p`.
+fn parse_pvar<'a, L: Language>(p: &str, tok: &'a [Token]) -> Result<(Pattern<L>, &'a [Token]), ParseError> {
+    // `?p[$x, $y]`, as opposed to the substitution `?p[?x := ?t]`.
+    if let [Token::LBracket, Token::RBracket | Token::Slot(_), ..] = tok {
+        return parse_ho_args(p, &tok[1..]);
+    }
+
+    let (excludes, tok) = match tok {
+        [Token::Backslash, Token::LBrace, rest @ ..] => (true, rest),
+        [Token::LBrace, rest @ ..] => (false, rest),
+        _ => return Ok((Pattern::PVar(p.to_string()), tok)),
+    };
+
+    let mut tok = tok;
+    let mut slots = Vec::new();
+    let mut open_ended = false;
+    loop {
+        match tok {
+            [Token::RBrace, ..] => break,
+            [Token::DotDot, ..] if !excludes => {
+                open_ended = true;
+                tok = &tok[1..];
+                let [Token::RBrace, ..] = tok else { return Err(ParseError::ExpectedRBrace(render(tok))) };
+                break;
+            },
+            [Token::Slot(s), ..] => {
+                slots.push(*s);
+                tok = &tok[1..];
+            },
+            _ => return Err(ParseError::ExpectedSlot(render(tok))),
+        }
+
+        match tok {
+            [Token::Comma, ..] => tok = &tok[1..],
+            [Token::RBrace, ..] => break,
+            _ => return Err(ParseError::ExpectedRBrace(render(tok))),
+        }
+    }
+    tok = &tok[1..];
+
+    let cond = match (excludes, open_ended) {
+        (true, _) => SlotCondition::Excludes(slots),
+        (false, false) => SlotCondition::Within(slots),
+        (false, true) => SlotCondition::Contains(slots),
+    };
+    Ok((Pattern::CondPVar(p.to_string(), cond), tok))
+}
+
+fn parse_ho_args<'a, L: Language>(p: &str, mut tok: &'a [Token]) -> Result<(Pattern<L>, &'a [Token]), ParseError> {
+    let mut args = Vec::new();
+    loop {
+        match tok {
+            [Token::RBracket, ..] => break,
+            [Token::Slot(s), ..] => {
+                args.push(*s);
+                tok = &tok[1..];
+            },
+            _ => return Err(ParseError::ExpectedSlot(render(tok))),
+        }
+
+        match tok {
+            [Token::Comma, ..] => tok = &tok[1..],
+            [Token::RBracket, ..] => break,
+            _ => return Err(ParseError::ExpectedRBracket(render(tok))),
+        }
+    }
+    tok = &tok[1..];
+
+    Ok((Pattern::HOPVar(p.to_string(), args), tok))
+}
+
 enum ChildImpl<L: Language> {
     Pattern(Pattern<L>),
     Slot(Slot),
 }
 
 fn parse_child<L: Language>(tok: &[Token]) -> Result<(ChildImpl<L>, &[Token]), ParseError> {
-    if let Token::Slot(slot) = tok[0] {
+    if let Some(Token::Slot(slot)) = tok.first().cloned() {
         return Ok((ChildImpl::Slot(slot), &tok[1..]));
     }
 
@@ -210,7 +340,24 @@ impl<L: Language> std::fmt::Display for Pattern<L> {
                 write!(f, ")")
             }
             Pattern::PVar(p) => write!(f, "?{p}"),
+            Pattern::CondPVar(p, cond) => {
+                let slots = cond.slot_list().iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ");
+                match cond {
+                    SlotCondition::Excludes(_) => write!(f, "?{p}\\{{{slots}}}"),
+                    SlotCondition::Within(_) => write!(f, "?{p}{{{slots}}}"),
+                    SlotCondition::Contains(v) if v.is_empty() => write!(f, "?{p}{{..}}"),
+                    SlotCondition::Contains(_) => write!(f, "?{p}{{{slots}, ..}}"),
+                }
+            },
+            Pattern::HOPVar(p, args) => {
+                let args = args.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ");
+                write!(f, "?{p}[{args}]")
+            },
             Pattern::Subst(b, x, t) => write!(f, "{b}[{x} := {t}]"),
+            Pattern::SubstMany(b, pairs) => {
+                let pairs = pairs.iter().map(|(x, t)| format!("{x} := {t}")).collect::<Vec<_>>().join(", ");
+                write!(f, "{b}[{pairs}]")
+            },
         }
     }
 }
@@ -234,6 +381,7 @@ impl<L: Language> std::fmt::Debug for RecExpr<L> {
 }
 
 
-fn to_vec<T: Clone>(t: &[T]) -> Vec<T> {
-    t.iter().cloned().collect()
+// The remaining tokens, for error messages that don't expose the private Token type.
+fn render(tok: &[Token]) -> String {
+    format!("{tok:?}")
 }
@@ -0,0 +1,96 @@
+use crate::*;
+
+/// The right-hand side of a [Rewrite]: applies the matches found by a [Searcher] to the e-graph.
+///
+/// Besides [Pattern], any closure `Fn(Vec<Subst>, &mut EGraph<L, N>)` is an Applier.
+pub trait Applier<L: Language, N: Analysis<L>> {
+    /// Applies the matches `substs` to the e-graph.
+    ///
+    /// `lhs` is the [Searcher::pattern] of the rewrite (if it has one), and `rule` is its name, which should be used as justification for the resulting unions.
+    fn apply(&self, substs: Vec<Subst>, lhs: Option<&Pattern<L>>, rule: &str, eg: &mut EGraph<L, N>);
+
+    /// The [Pattern] that this Applier instantiates, if there is one.
+    fn pattern(&self) -> Option<&Pattern<L>> { None }
+}
+
+// A Pattern applier unions the instantiations of `lhs` and `self`.
+impl<L: Language, N: Analysis<L>> Applier<L, N> for Pattern<L> {
+    fn apply(&self, substs: Vec<Subst>, lhs: Option<&Pattern<L>>, rule: &str, eg: &mut EGraph<L, N>) {
+        let lhs = lhs.unwrap_or_else(|| panic!("rule `{rule}`: a Pattern applier requires a Searcher with a Pattern!"));
+        for subst in substs {
+            if eg.check_budget() { break; }
+            eg.union_instantiations(lhs, self, &subst, Some(rule.to_string()));
+        }
+    }
+
+    fn pattern(&self) -> Option<&Pattern<L>> { Some(self) }
+}
+
+impl<L, N, F> Applier<L, N> for F
+where
+    L: Language,
+    N: Analysis<L>,
+    F: Fn(Vec<Subst>, &mut EGraph<L, N>),
+{
+    fn apply(&self, substs: Vec<Subst>, _lhs: Option<&Pattern<L>>, _rule: &str, eg: &mut EGraph<L, N>) {
+        self(substs, eg)
+    }
+}
+
+/// An [Applier] that only applies those matches, for which `cond` holds.
+///
+/// The condition of each match is checked right before it is applied, so it sees the effects of the previous matches.
+pub struct ConditionalApplier<C, A> {
+    pub cond: C,
+    pub applier: A,
+}
+
+impl<L, N, C, A> Applier<L, N> for ConditionalApplier<C, A>
+where
+    L: Language,
+    N: Analysis<L>,
+    C: Fn(&Subst, &EGraph<L, N>) -> bool,
+    A: Applier<L, N>,
+{
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
+    fn apply(&self, substs: Vec<Subst>, lhs: Option<&Pattern<L>>, rule: &str, eg: &mut EGraph<L, N>) {
+        for subst in substs {
+            if eg.check_budget() { break; }
+            if (self.cond)(&subst, eg) {
+                self.applier.apply(vec![subst], lhs, rule, eg);
+            }
+        }
+    }
+
+    fn pattern(&self) -> Option<&Pattern<L>> { self.applier.pattern() }
+}
+
+/// An [Applier] that computes the right-hand side of a rule programmatically.
+///
+/// For each match, the closure receives the [Subst] (using the slot names of the lhs pattern) and builds the right-hand side, e.g. using [EGraph::add] or [pattern_subst].
+/// Binders that are introduced by the right-hand side should use [Slot::fresh].
+/// If the closure returns `Some(app_id)`, then `app_id` is unioned with the instantiated lhs, justified by the name of the rule.
+pub struct DynApplier<F>(pub F);
+
+impl<L, N, F> Applier<L, N> for DynApplier<F>
+where
+    L: Language,
+    N: Analysis<L>,
+    F: Fn(&Subst, &mut EGraph<L, N>) -> Option<AppliedId>,
+{
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
+    fn apply(&self, substs: Vec<Subst>, lhs: Option<&Pattern<L>>, rule: &str, eg: &mut EGraph<L, N>) {
+        let lhs = lhs.unwrap_or_else(|| panic!("rule `{rule}`: a DynApplier requires a Searcher with a Pattern!"));
+        for subst in substs {
+            if eg.check_budget() { break; }
+            let Some(b) = (self.0)(&subst, eg) else { continue };
+            let a = pattern_subst(eg, lhs, &subst);
+
+            // The rhs can't have free slots that the lhs doesn't have. Otherwise these slots would just become redundant.
+            let leaked = &b.slots() - &a.slots();
+            assert!(leaked.is_empty(), "rule `{rule}`: the right-hand side has free slots {leaked:?}, which are not free in the left-hand side!");
+
+            eg.union_justified(&a, &b, Some(rule.to_string()));
+        }
+    }
+}
@@ -9,22 +9,151 @@ struct State {
 
     // maps from the egraph slots to the pattern slots.
     partial_slotmap: SlotMap,
+
+    // the egraph slots fixed by `ematch_class`. They are mapped to themselves, and can't be bound by the pattern.
+    fixed: HashSet<Slot>,
+
+    // the matches of higher-order pattern-variables `?f[$x, $y]`, using egraph slots.
+    // They can only be renamed & checked once the slotmap is complete, see `final_subst`.
+    ho_matches: Vec<(String, Vec<Slot>, AppliedId)>,
+
+    // the matches of renaming substitutions `?b[(var $x) := (var $y)]` as `(b, $x, $y, i)`, using egraph slots for `i`.
+    renamings: Vec<(String, Slot, Slot, AppliedId)>,
 }
 
 #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
 pub fn ematch_all<L: Language, N: Analysis<L>>(eg: &EGraph<L, N>, pattern: &Pattern<L>) -> Vec<Subst> {
+    ematch_ids(eg, pattern, &eg.ids())
+}
+
+// Like [ematch_all], but only finds the matches in the e-classes `ids`.
+pub(crate) fn ematch_ids<L: Language, N: Analysis<L>>(eg: &EGraph<L, N>, pattern: &Pattern<L>, ids: &[Id]) -> Vec<Subst> {
+    // The slot conditions are checked early during matching if possible, but some can only be decided on the final subst.
+    let conds = pattern.slot_conditions();
+
     let mut out = Vec::new();
-    for i in eg.ids() {
+    for &i in ids {
+        if eg.check_budget() { break; }
         let i = eg.mk_sem_identity_applied_id(i);
-        out.extend(
-            ematch_impl(pattern, State::default(), i, eg)
-                .into_iter()
-                .map(final_subst)
-        );
+        out.extend(ematch_class_impl(eg, pattern, State::default(), i, &conds));
     }
     out
 }
 
+/// Like [ematch_all], but only finds the matches in the e-class of `i`.
+///
+/// The free slots of the matched terms have to be the slots of `i`, under the same names.
+/// So `pattern_subst` on a returned subst yields a term equal to `i`.
+///
+/// Slots bound by the pattern may share their names with the slots of `i`.
+/// But a match is skipped if some pattern-variable would need both such a bound slot and the free slot of the same name.
+pub fn ematch_class<L: Language, N: Analysis<L>>(eg: &EGraph<L, N>, pattern: &Pattern<L>, i: &AppliedId) -> Vec<Subst> {
+    let i = eg.find_applied_id(i);
+    let fixed = i.slots();
+
+    // The bound slots of the pattern are renamed apart from the fixed slots, and renamed back in the end.
+    let mut back = Vec::new();
+    let pattern = rename_bound_apart(pattern, &fixed, &mut back);
+    let conds = pattern.slot_conditions();
+
+    let st = State {
+        partial_slotmap: SlotMap::identity(&fixed),
+        fixed,
+        ..State::default()
+    };
+    ematch_class_impl(eg, &pattern, st, i, &conds)
+        .into_iter()
+        .filter_map(|subst| rename_subst_back(subst, &back))
+        .collect()
+}
+
+// Renames the slots bound in `pattern` that occur in `avoid` to fresh slots, and pushes the pairs `(fresh, original)` to `back`.
+fn rename_bound_apart<L: Language>(pattern: &Pattern<L>, avoid: &HashSet<Slot>, back: &mut Vec<(Slot, Slot)>) -> Pattern<L> {
+    match pattern {
+        Pattern::ENode(n, children) => {
+            let pairs: Vec<(Slot, Slot)> = n.private_slots().into_iter()
+                .filter(|s| avoid.contains(s))
+                .map(|s| (s, Slot::fresh()))
+                .collect();
+            back.extend(pairs.iter().map(|(x, y)| (*y, *x)));
+
+            let mut n = n.clone();
+            for s in n.all_slot_occurences_mut() {
+                *s = rename_slot(*s, &pairs);
+            }
+            let children = children.iter()
+                .map(|c| rename_bound_apart(&rename_pattern_slots(c, &pairs), avoid, back))
+                .collect();
+            Pattern::ENode(n, children)
+        },
+        Pattern::PVar(_) | Pattern::CondPVar(..) | Pattern::HOPVar(..) => pattern.clone(),
+        Pattern::Subst(b, x, t) => {
+            let b = rename_bound_apart(b, avoid, back);
+            let x = rename_bound_apart(x, avoid, back);
+            let t = rename_bound_apart(t, avoid, back);
+            Pattern::Subst(Box::new(b), Box::new(x), Box::new(t))
+        },
+        Pattern::SubstMany(b, pairs) => {
+            let b = rename_bound_apart(b, avoid, back);
+            let pairs = pairs.iter().map(|(x, t)| (rename_bound_apart(x, avoid, back), rename_bound_apart(t, avoid, back))).collect();
+            Pattern::SubstMany(Box::new(b), pairs)
+        },
+    }
+}
+
+// Renames all slot occurences in `pattern` according to `pairs`.
+fn rename_pattern_slots<L: Language>(pattern: &Pattern<L>, pairs: &[(Slot, Slot)]) -> Pattern<L> {
+    let r = |v: &[Slot]| v.iter().map(|s| rename_slot(*s, pairs)).collect::<Vec<_>>();
+    match pattern {
+        Pattern::ENode(n, children) => {
+            let mut n = n.clone();
+            for s in n.all_slot_occurences_mut() {
+                *s = rename_slot(*s, pairs);
+            }
+            Pattern::ENode(n, children.iter().map(|c| rename_pattern_slots(c, pairs)).collect())
+        },
+        Pattern::PVar(_) => pattern.clone(),
+        Pattern::CondPVar(v, cond) => {
+            let cond = match cond {
+                SlotCondition::Excludes(s) => SlotCondition::Excludes(r(s)),
+                SlotCondition::Within(s) => SlotCondition::Within(r(s)),
+                SlotCondition::Contains(s) => SlotCondition::Contains(r(s)),
+            };
+            Pattern::CondPVar(v.clone(), cond)
+        },
+        Pattern::HOPVar(v, args) => Pattern::HOPVar(v.clone(), r(args)),
+        Pattern::Subst(b, x, t) => {
+            let [b, x, t] = [b, x, t].map(|p| Box::new(rename_pattern_slots(p, pairs)));
+            Pattern::Subst(b, x, t)
+        },
+        Pattern::SubstMany(b, pairs2) => {
+            let pairs2 = pairs2.iter().map(|(x, t)| (rename_pattern_slots(x, pairs), rename_pattern_slots(t, pairs))).collect();
+            Pattern::SubstMany(Box::new(rename_pattern_slots(b, pairs)), pairs2)
+        },
+    }
+}
+
+fn rename_slot(s: Slot, pairs: &[(Slot, Slot)]) -> Slot {
+    pairs.iter().find(|(x, _)| *x == s).map(|(_, y)| *y).unwrap_or(s)
+}
+
+// Undoes `rename_bound_apart` on a subst, or returns None if this would merge a bound slot with a free slot of the same name.
+fn rename_subst_back(subst: Subst, back: &[(Slot, Slot)]) -> Option<Subst> {
+    subst.into_iter().map(|(v, i)| {
+        let slots = i.slots();
+        if back.iter().any(|(x, y)| slots.contains(x) && slots.contains(y)) { return None; }
+        Some((v, rename_slots(&i, back)))
+    }).collect()
+}
+
+fn ematch_class_impl<L: Language, N: Analysis<L>>(eg: &EGraph<L, N>, pattern: &Pattern<L>, st: State, i: AppliedId, conds: &[(String, SlotCondition)]) -> Vec<Subst> {
+    ematch_impl(pattern, st, i, eg)
+        .into_iter()
+        .flat_map(|st| final_subst(st, eg))
+        .filter(|subst| conds.iter().all(|(v, cond)| cond.holds(&subst[v].slots())))
+        .collect()
+}
+
 // `i` uses egraph slots instead of pattern slots.
 fn ematch_impl<L: Language, N: Analysis<L>>(pattern: &Pattern<L>, st: State, i: AppliedId, eg: &EGraph<L, N>) -> Vec<State> {
     match &pattern {
@@ -37,6 +166,18 @@ fn ematch_impl<L: Language, N: Analysis<L>>(pattern: &Pattern<L>, st: State, i:
             }
             vec![st]
         },
+        Pattern::CondPVar(v, cond) => {
+            if refutes(cond, &i, &st.partial_slotmap) { return Vec::new(); }
+            ematch_impl(&Pattern::PVar(v.clone()), st, i, eg)
+        },
+        Pattern::HOPVar(v, args) => {
+            // The fixed slots of `ematch_class` are not bound by the pattern.
+            let bound_elsewhere = |s: &Slot| !st.fixed.contains(s) && st.partial_slotmap.get(*s).is_some_and(|p| !args.contains(&p));
+            if i.slots().iter().any(bound_elsewhere) { return Vec::new(); }
+            let mut st = st;
+            st.ho_matches.push((v.clone(), args.clone(), i));
+            vec![st]
+        },
         Pattern::ENode(n, children) => {
             let mut out = Vec::new();
             for nn in eg.enodes_applied(&i) {
@@ -76,7 +217,44 @@ fn ematch_impl<L: Language, N: Analysis<L>>(pattern: &Pattern<L>, st: State, i:
             }
             out
         },
-        Pattern::Subst(..) => panic!(),
+        Pattern::Subst(..) | Pattern::SubstMany(..) => {
+            let Some((v, x, y)) = pattern.subst_parts().and_then(|(b, pairs)| match pairs[..] {
+                [(x, t)] => renaming_subst(b, x, t),
+                _ => None,
+            }) else {
+                panic!("unsupported substitution `{pattern}` in the lhs of a pattern, see `Rewrite::try_new`")
+            };
+            let mut st = st;
+            st.renamings.push((v, x, y, i));
+            vec![st]
+        },
+    }
+}
+
+// Decomposes the substitution `?b[(var $x) := (var $y)]` (or `?b\{..}[(var $x) := (var $y)]`) into `(b, $x, $y)`.
+// We only support substituting a single variable by another variable (i.e. renaming) in the lhs, as this is decidable for slotted e-classes.
+pub(crate) fn renaming_subst<L: Language>(b: &Pattern<L>, x: &Pattern<L>, t: &Pattern<L>) -> Option<(String, Slot, Slot)> {
+    let (Pattern::PVar(v) | Pattern::CondPVar(v, _)) = b else { return None };
+    let (Pattern::ENode(nx, cx), Pattern::ENode(nt, ct)) = (x, t) else { return None };
+    if !cx.is_empty() || !ct.is_empty() { return None; }
+
+    let (sx, st) = (nx.all_slot_occurences(), nt.all_slot_occurences());
+    if sx.len() != 1 || st.len() != 1 { return None; }
+
+    // `x` and `t` need to be the same kind of variable.
+    if nx.weak_shape().0 != nt.weak_shape().0 { return None; }
+
+    Some((v.clone(), sx[0], st[0]))
+}
+
+// Checks whether `cond` is already violated by `i`, given the current partial slotmap.
+// Slots of `i` that are not yet mapped to pattern slots are undecided, and will be checked on the final subst.
+fn refutes(cond: &SlotCondition, i: &AppliedId, partial_slotmap: &SlotMap) -> bool {
+    let slots = i.slots();
+    match cond {
+        SlotCondition::Excludes(v) => slots.iter().any(|s| partial_slotmap.get(*s).map(|p| v.contains(&p)).unwrap_or(false)),
+        SlotCondition::Within(v) => slots.iter().any(|s| partial_slotmap.get(*s).map(|p| !v.contains(&p)).unwrap_or(false)),
+        SlotCondition::Contains(v) => partial_slotmap.iter().any(|(s, p)| v.contains(&p) && !slots.contains(&s)),
     }
 }
 
@@ -96,12 +274,18 @@ fn try_insert_compatible_slotmap_bij(k: Slot, v: Slot, map: &mut SlotMap) -> boo
     map.is_bijection()
 }
 
-fn final_subst(s: State) -> Subst {
+fn final_subst<L: Language, N: Analysis<L>>(s: State, eg: &EGraph<L, N>) -> Vec<Subst> {
     let State {
         partial_subst: mut subst,
-        partial_slotmap: mut slotmap
+        partial_slotmap: mut slotmap,
+        fixed,
+        ho_matches,
+        renamings,
     } = s;
 
+    // The slots bound by the pattern, excluding the slots fixed by `ematch_class`.
+    let bound: HashSet<Slot> = slotmap.iter().filter(|(x, _)| !fixed.contains(x)).map(|(_, y)| y).collect();
+
     // Previously, the subst uses `egraph`-based slot names.
     // Afterwards, the subst uses `pattern`-based slot names.
     for (_, v) in subst.iter_mut() {
@@ -115,5 +299,64 @@ fn final_subst(s: State) -> Subst {
         *v = v.apply_slotmap(&slotmap);
     }
 
-    subst
+    for (v, args, i) in ho_matches {
+        for s in i.slots() {
+            if !slotmap.contains_key(s) {
+                slotmap.insert(s, Slot::fresh());
+            }
+        }
+        let i = i.apply_slotmap(&slotmap);
+
+        // `?f[$x, $y]` may not depend on any other slot bound by the pattern.
+        if i.slots().iter().any(|s| bound.contains(s) && !args.contains(s)) { return Vec::new(); }
+
+        let pairs: Vec<_> = args.iter().enumerate().map(|(j, x)| (*x, Slot::numeric(j as _))).collect();
+
+        // The arguments are stored as `$0, $1, ...`, which can't be told apart from free slots of the same names.
+        if i.slots().iter().any(|s| !args.contains(s) && pairs.iter().any(|(_, y)| y == s)) { return Vec::new(); }
+        let i = rename_slots(&i, &pairs);
+
+        if !insert_compatible(&mut subst, v, i, eg) { return Vec::new(); }
+    }
+
+    let mut out = vec![subst];
+    for (v, x, y, i) in renamings {
+        for s in i.slots() {
+            if !slotmap.contains_key(s) {
+                slotmap.insert(s, Slot::fresh());
+            }
+        }
+        let i = i.apply_slotmap(&slotmap);
+
+        // `b[x := y]` can only equal `i` if `x` is not free in `i`.
+        if x != y && i.slots().contains(&x) { return Vec::new(); }
+
+        // `check_lhs` guarantees that `y` is not free in `b` (or that `x == y`),
+        // hence `i[y := x]` is the only preimage of `i`.
+        let b = rename_slots(&i, &[(y, x)]);
+        out.retain_mut(|subst| insert_compatible(subst, v.clone(), b.clone(), eg));
+    }
+
+    out
+}
+
+// Binds `v` to `i`, or checks that it is already bound to an equal e-class.
+fn insert_compatible<L: Language, N: Analysis<L>>(subst: &mut Subst, v: String, i: AppliedId, eg: &EGraph<L, N>) -> bool {
+    if let Some(j) = subst.get(&v) {
+        eg.eq(&i, j)
+    } else {
+        subst.insert(v, i);
+        true
+    }
+}
+
+// Renames the slots of `i` according to `pairs`, and leaves all other slots as they are.
+pub(crate) fn rename_slots(i: &AppliedId, pairs: &[(Slot, Slot)]) -> AppliedId {
+    let mut m = SlotMap::identity(&i.slots());
+    for (x, y) in pairs {
+        if m.contains_key(*x) {
+            m.insert(*x, *y);
+        }
+    }
+    i.apply_slotmap(&m)
 }
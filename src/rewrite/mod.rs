@@ -1,5 +1,6 @@
 use crate::*;
-use std::any::Any;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 mod ematch;
 pub use ematch::*;
@@ -10,18 +11,137 @@ pub use pattern::*;
 mod subst_method;
 pub use subst_method::*;
 
+mod searcher;
+pub use searcher::*;
+
+mod applier;
+pub use applier::*;
+
 /// An equational rewrite rule.
+///
+/// A Rewrite consists of a [Searcher] that finds the matches of the rule, and an [Applier] that applies them.
+/// Alternatively, it can be built from a [RewriteT].
 pub struct Rewrite<L: Language, N: Analysis<L> = ()> {
-    pub(crate) searcher: Box<dyn Fn(&EGraph<L, N>) -> Box<dyn Any>>,
-    pub(crate) applier: Box<dyn Fn(Box<dyn Any>, &mut EGraph<L, N>)>,
+    pub(crate) name: String,
+    pub(crate) imp: RewriteImpl<L, N>,
+
+    // If set, this rule uses its own [SubstMethod] instead of the one of the e-graph.
+    pub(crate) subst_method: Option<SubstMethodCtor<L, N>>,
+
+    pub(crate) priority: i32,
+}
+
+type SubstMethodCtor<L, N> = Box<dyn Fn() -> Box<dyn SubstMethod<L, N>>>;
+
+pub(crate) enum RewriteImpl<L: Language, N: Analysis<L>> {
+    Parts(Box<dyn Searcher<L, N>>, Box<dyn Applier<L, N>>),
+
+    // The searcher of a [RewriteT], which binds its result to the applier.
+    Custom(CustomSearcher<L, N>),
 }
 
+type CustomSearcher<L, N> = Box<dyn Fn(&EGraph<L, N>) -> PendingApply<L, N>>;
+type PendingApply<L, N> = Box<dyn FnOnce(&mut EGraph<L, N>)>;
+type CustomApplier<L, N, T> = dyn Fn(T, &mut EGraph<L, N>);
+
+/// The matches of a [Rewrite], as returned by [Rewrite::search] and consumed by [Rewrite::apply].
+pub enum Matches<L: Language, N: Analysis<L>> {
+    /// The matches of a rule consisting of a [Searcher] and an [Applier].
+    Substs(Vec<Subst>),
+
+    /// The search result of a [RewriteT], together with the applier that consumes it.
+    Custom(PendingApply<L, N>),
+}
+
+impl<L: Language, N: Analysis<L>> Matches<L, N> {
+    /// No matches at all.
+    pub fn none() -> Self {
+        Matches::Substs(Vec::new())
+    }
+
+    /// The number of matches.
+    ///
+    /// The search result of a [RewriteT] is opaque, so it counts as a single match.
+    pub fn len(&self) -> usize {
+        match self {
+            Matches::Substs(substs) => substs.len(),
+            Matches::Custom(_) => 1,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The matches as [Subst]s. This is empty for the search result of a [RewriteT].
+    pub fn substs(&self) -> &[Subst] {
+        match self {
+            Matches::Substs(substs) => substs,
+            Matches::Custom(_) => &[],
+        }
+    }
+}
+
+#[derive(Debug)]
+/// The error type for constructing a [Rewrite], see [Rewrite::try_new].
+pub struct RewriteError {
+    /// The name of the offending rule.
+    pub rule: String,
+    pub kind: RewriteErrorKind,
+}
+
+#[derive(Debug)]
+/// What went wrong when constructing a [Rewrite].
+pub enum RewriteErrorKind {
+    /// The lhs failed to parse.
+    LhsParse(ParseError),
+
+    /// The rhs failed to parse.
+    RhsParse(ParseError),
+
+    /// The lhs contains a construct that the e-matcher doesn't support.
+    /// Currently, the only substitutions supported in the lhs are renamings like `?b\{$y}[(var $x) := (var $y)]`,
+    /// where `$y` may not be free in `?b`, see [Pattern::check_lhs].
+    UnsupportedLhs(String),
+
+    /// The lhs is a bare pattern-variable like `?x`, and hence matches every e-class.
+    /// Use [Rewrite::new_bare] if this is intended.
+    BareLhs,
+
+    /// The rhs uses a pattern-variable that is not bound in the lhs.
+    UnboundVar(String),
+
+    /// A pattern-variable is used both as `?x` and as `?x[..]`, or with different numbers of slot arguments.
+    VarKindMismatch(String),
+
+    /// The rhs has a free slot that is not free in the lhs.
+    /// Slots that only occur in the rhs need to be bound by the rhs, they are then instantiated with fresh slots.
+    FreeRhsSlot(Slot),
+}
+
+impl std::fmt::Display for RewriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rule = &self.rule;
+        match &self.kind {
+            RewriteErrorKind::LhsParse(e) => write!(f, "rule `{rule}`: failed to parse the lhs: {e}"),
+            RewriteErrorKind::RhsParse(e) => write!(f, "rule `{rule}`: failed to parse the rhs: {e}"),
+            RewriteErrorKind::UnsupportedLhs(p) => write!(f, "rule `{rule}`: unsupported construct `{p}` in the lhs"),
+            RewriteErrorKind::BareLhs => write!(f, "rule `{rule}`: the lhs is a bare pattern-variable, use `Rewrite::new_bare` if this is intended"),
+            RewriteErrorKind::UnboundVar(v) => write!(f, "rule `{rule}`: the pattern-variable `?{v}` is not bound in the lhs"),
+            RewriteErrorKind::VarKindMismatch(v) => write!(f, "rule `{rule}`: the pattern-variable `?{v}` is used with different slot arguments"),
+            RewriteErrorKind::FreeRhsSlot(s) => write!(f, "rule `{rule}`: the slot `{s}` is free in the rhs, but not in the lhs"),
+        }
+    }
+}
+
+impl Error for RewriteError {}
+
 /// Use this type when you want to build your own [Rewrite].
 ///
 /// The type parameter `T` can be anything you want, as long as the `searcher` creates it, and the `applier` consumes it.
 ///
 /// In most cases, `T` is a [Subst].
-pub struct RewriteT<L: Language, N: Analysis<L>, T: Any> {
+pub struct RewriteT<L: Language, N: Analysis<L>, T> {
     pub searcher: Box<dyn Fn(&EGraph<L, N>) -> T>,
     pub applier: Box<dyn Fn(T, &mut EGraph<L, N>)>,
 }
@@ -29,73 +149,219 @@ pub struct RewriteT<L: Language, N: Analysis<L>, T: Any> {
 
 impl<L: Language + 'static, N: Analysis<L> + 'static, T: 'static> RewriteT<L, N, T> {
     /// Use this function to convert it to an actual [Rewrite].
+    ///
+    /// The resulting rule gets a generated name like `<RewriteT #3>`, which is unique within this process.
+    /// Use [Rewrite::with_name] to give it a meaningful one.
+    /// As `T` is opaque, its search result counts as a single match, see [Matches].
     pub fn into(self) -> Rewrite<L, N> {
         let searcher = self.searcher;
-        let applier = self.applier;
-        Rewrite {
-            searcher: Box::new(move |eg| Box::new((*searcher)(eg))),
-            applier: Box::new(move |t, eg| (*applier)(any_to_t(t), eg))
-        }
+        let applier: Rc<CustomApplier<L, N, T>> = Rc::from(self.applier);
+        let imp = RewriteImpl::Custom(Box::new(move |eg| {
+            let t = (*searcher)(eg);
+            let applier = applier.clone();
+            Box::new(move |eg: &mut EGraph<L, N>| (*applier)(t, eg))
+        }));
+        let n = REWRITE_T_COUNT.fetch_add(1, Ordering::Relaxed);
+        Rewrite::from_impl(&format!("<RewriteT #{n}>"), imp)
     }
 }
 
-fn any_to_t<T: Any>(t: Box<dyn Any>) -> T {
-    *t.downcast().unwrap()
-}
+// The number of [RewriteT]s converted so far, used to name them.
+static REWRITE_T_COUNT: AtomicUsize = AtomicUsize::new(0);
 
 /// Applies each given rewrite rule to the E-Graph once.
 /// Returns an indicator for whether the e-graph changed as a result.
 #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
 pub fn apply_rewrites<L: Language, N: Analysis<L>>(eg: &mut EGraph<L, N>, rewrites: &[Rewrite<L, N>]) -> bool {
-    let prog = eg.progress();
+    let prog = eg.modification_count();
 
-    let ts: Vec<Box<dyn Any>> = rewrites.iter().map(|rw| (*rw.searcher)(eg)).collect();
+    let ts: Vec<Matches<L, N>> = rewrites.iter().map(|rw| rw.search(eg)).collect();
     for (rw, t) in rewrites.iter().zip(ts.into_iter()) {
-        (*rw.applier)(t, eg);
+        rw.apply(t, eg);
     }
 
-    prog != eg.progress()
+    prog != eg.modification_count()
 }
 
 impl<L: Language + 'static, N: Analysis<L> + 'static> Rewrite<L, N> {
     /// Create a rewrite rule by specifing a left- and right-hand side of your equation.
+    ///
+    /// Panics if the rule is invalid, see [Rewrite::try_new] for the fallible version.
     pub fn new(rule: &str, a: &str, b: &str) -> Self {
-        Self::new_if(rule, a, b, |_, _| true)
+        Self::try_new(rule, a, b).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Create a rewrite rule, or return an error if the rule is invalid.
+    ///
+    /// Besides parsing, this checks that
+    /// - the lhs is supported by the e-matcher, and is not a bare pattern-variable `?x`,
+    /// - every pattern-variable of the rhs is bound in the lhs,
+    /// - every free slot of the rhs is free in the lhs.
+    pub fn try_new(rule: &str, a: &str, b: &str) -> Result<Self, RewriteError> {
+        let (a, b) = Self::parse_rule(rule, a, b, false)?;
+        Ok(Self::from_parts(rule, a, b))
+    }
+
+    /// Create a rewrite rule whose lhs is a bare pattern-variable `?x`, like eta-expansion.
+    ///
+    /// Such a rule matches every e-class, so use it with care.
+    /// Panics if the rule is invalid, see [Rewrite::try_new_bare] for the fallible version.
+    pub fn new_bare(rule: &str, a: &str, b: &str) -> Self {
+        Self::try_new_bare(rule, a, b).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Create a rewrite rule whose lhs may be a bare pattern-variable, or return an error if the rule is otherwise invalid.
+    pub fn try_new_bare(rule: &str, a: &str, b: &str) -> Result<Self, RewriteError> {
+        let (a, b) = Self::parse_rule(rule, a, b, true)?;
+        Ok(Self::from_parts(rule, a, b))
     }
 
     /// Create a conditional rewrite rule.
     pub fn new_if(rule: &str, a: &str, b: &str, cond: impl Fn(&Subst, &EGraph<L, N>) -> bool + 'static) -> Self {
-        let a = Pattern::parse(a).unwrap();
-        let b = Pattern::parse(b).unwrap();
-        let rule = rule.to_string();
-        let a2 = a.clone();
-        RewriteT {
-            searcher: Box::new(move |eg| ematch_all(eg, &a)),
-            applier: Box::new(move |substs, eg| {
-                Self::apply_substs_cond(substs, &cond, &a2, &b, &rule, eg)
-            }),
-        }.into()
+        Self::try_new_if(rule, a, b, cond).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Create a conditional rewrite rule, or return an error if the rule is invalid.
+    pub fn try_new_if(rule: &str, a: &str, b: &str, cond: impl Fn(&Subst, &EGraph<L, N>) -> bool + 'static) -> Result<Self, RewriteError> {
+        let (a, b) = Self::parse_rule(rule, a, b, false)?;
+        Ok(Self::from_parts(rule, a, ConditionalApplier { cond, applier: b }))
+    }
+
+    /// Create a rewrite rule, whose right-hand side is computed by a closure.
+    ///
+    /// See [DynApplier] for details.
+    /// Panics if the lhs is invalid, see [Rewrite::try_new_dyn] for the fallible version.
+    pub fn new_dyn(rule: &str, a: &str, f: impl Fn(&Subst, &mut EGraph<L, N>) -> Option<AppliedId> + 'static) -> Self {
+        Self::try_new_dyn(rule, a, f).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Create a rewrite rule, whose right-hand side is computed by a closure, or return an error if the lhs is invalid.
+    pub fn try_new_dyn(rule: &str, a: &str, f: impl Fn(&Subst, &mut EGraph<L, N>) -> Option<AppliedId> + 'static) -> Result<Self, RewriteError> {
+        let err = |kind| RewriteError { rule: rule.to_string(), kind };
+        let a = Pattern::parse_lhs(a).map_err(err)?;
+        a.check_not_bare().map_err(err)?;
+        Ok(Self::from_parts(rule, a, DynApplier(f)))
+    }
+
+    fn parse_rule(rule: &str, a: &str, b: &str, allow_bare: bool) -> Result<(Pattern<L>, Pattern<L>), RewriteError> {
+        let err = |kind| RewriteError { rule: rule.to_string(), kind };
+
+        let a = Pattern::parse_lhs(a).map_err(err)?;
+        if !allow_bare {
+            a.check_not_bare().map_err(err)?;
+        }
+        let b = Pattern::parse(b).map_err(|e| err(RewriteErrorKind::RhsParse(e)))?;
+        b.check_rhs(&a).map_err(err)?;
+        Ok((a, b))
+    }
+
+    /// Create a rewrite rule from an arbitrary [Searcher] and [Applier].
+    pub fn from_parts(rule: &str, searcher: impl Searcher<L, N> + 'static, applier: impl Applier<L, N> + 'static) -> Self {
+        Self::from_impl(rule, RewriteImpl::Parts(Box::new(searcher), Box::new(applier)))
+    }
+
+    fn from_impl(rule: &str, imp: RewriteImpl<L, N>) -> Self {
+        Rewrite {
+            name: rule.to_string(),
+            imp,
+            subst_method: None,
+            priority: 0,
+        }
+    }
+
+    /// Use the [SubstMethod] `S` for the substitutions `b[x := t]` of this rule.
+    ///
+    /// By default, a rule uses the [SubstMethod] of the e-graph, see [EGraph::with_subst_method].
+    /// The choice is made per rule: every [Pattern::Subst] and [Pattern::SubstMany] in the rhs of this rule uses `S`.
+    /// A new instance of `S` is created for each application of the rule, and shared by all of its matches.
+    pub fn with_subst_method<S: SubstMethod<L, N>>(mut self) -> Self {
+        let ctor: fn() -> Box<dyn SubstMethod<L, N>> = S::new_boxed;
+        self.subst_method = Some(Box::new(ctor));
+        self
+    }
+
+    /// Use copies of `method` for the substitutions `b[x := t]` of this rule.
+    ///
+    /// Like [Rewrite::with_subst_method], but for a configured method,
+    /// e.g. an [AlgorithmSubst] with a [LocalSearchExtraction] that does a certain number of rounds.
+    /// A new copy of `method` is used for each application of the rule, and shared by all of its matches.
+    pub fn with_subst_method_instance<S: SubstMethod<L, N> + Clone + 'static>(mut self, method: S) -> Self {
+        self.subst_method = Some(Box::new(move || Box::new(method.clone())));
+        self
+    }
+}
+
+impl<L: Language, N: Analysis<L>> Rewrite<L, N> {
+    /// The name of this rule.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Renames this rule.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Sets the priority of this rule, the default is 0.
+    ///
+    /// Rules with lower priority are only applied once the rules with higher priority stop changing the e-graph, see [apply_rewrites_with].
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// The priority of this rule.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// The left-hand side [Pattern] of this rule, if it has one.
+    pub fn lhs(&self) -> Option<&Pattern<L>> {
+        match &self.imp {
+            RewriteImpl::Parts(searcher, _) => searcher.pattern(),
+            RewriteImpl::Custom(_) => None,
+        }
+    }
+
+    /// The right-hand side [Pattern] of this rule, if it has one.
+    pub fn rhs(&self) -> Option<&Pattern<L>> {
+        match &self.imp {
+            RewriteImpl::Parts(_, applier) => applier.pattern(),
+            RewriteImpl::Custom(_) => None,
+        }
     }
 
+    /// Finds all matches of this rule in the e-graph, without applying them.
     #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
-    fn apply_substs_cond(
-        substs: Vec<Subst>,
-        cond: &(impl Fn(&Subst, &EGraph<L, N>) -> bool + 'static),
-        a: &Pattern<L>,
-        b: &Pattern<L>,
-        rule: &str,
-        eg: &mut EGraph<L, N>
-    ) {
-        for subst in substs {
-            if cond(&subst, eg) {
-                eg.union_instantiations(a, b, &subst, Some(rule.to_string()));
-            }
+    pub fn search(&self, eg: &EGraph<L, N>) -> Matches<L, N> {
+        match &self.imp {
+            RewriteImpl::Parts(searcher, _) => Matches::Substs(searcher.search(eg)),
+            RewriteImpl::Custom(searcher) => Matches::Custom(searcher(eg)),
+        }
+    }
+
+    /// Applies the matches (typically obtained from [Rewrite::search] of the same rule) to the e-graph.
+    #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
+    pub fn apply(&self, matches: Matches<L, N>, eg: &mut EGraph<L, N>) {
+        // The rule-specific subst-method temporarily replaces the one of the e-graph.
+        let old = self.subst_method.as_ref().map(|f| eg.subst_method.replace(f()));
+        if let Some(method) = &mut eg.subst_method {
+            method.reset();
+        }
+        match (&self.imp, matches) {
+            (RewriteImpl::Parts(searcher, applier), Matches::Substs(substs)) => applier.apply(substs, searcher.pattern(), &self.name, eg),
+            (_, Matches::Custom(pending)) => pending(eg),
+            (RewriteImpl::Custom(_), Matches::Substs(substs)) => assert!(substs.is_empty(), "rule `{}`: a RewriteT can't apply Substs!", self.name),
+        }
+        if let Some(old) = old {
+            eg.subst_method = old;
         }
     }
 }
 
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 /// A Progress Measure to check saturation of an e-graph with.
 pub struct ProgressMeasure {
     /// How many classes that were allocated in this e-graph. This measure is strictly growing.
@@ -114,6 +380,8 @@ pub struct ProgressMeasure {
 
 impl<L: Language, N: Analysis<L>> EGraph<L, N> {
     /// Computes the [ProgressMeasure] of this E-Graph.
+    ///
+    /// This iterates over all e-classes. To cheaply check whether the e-graph changed, use [EGraph::modification_count].
     #[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
     pub fn progress(&self) -> ProgressMeasure {
         let ids = self.ids();
@@ -4,16 +4,226 @@ use crate::*;
 /// A Pattern to match against, or as the rhs of a rewrite rule.
 ///
 /// - It supports pattern-variables `?x` to match against anything.
-/// - It supports (on the rhs) substitutions `b[x := t]` to substitute natively.
+/// - It supports pattern-variables with a [SlotCondition], like `?x\{$1}`, to restrict which slots may be free in the match.
+/// - It supports higher-order pattern-variables `?f[$x, $y]`, which may only depend on the listed slots (of all slots bound in the lhs).
+///   They are renamed upon instantiation, so `?f[$z]` on the rhs replaces the slot `$x` of a match `?f[$x]` by `$z`.
+/// - It supports (on the rhs) substitutions `b[x := t]` to substitute natively, and simultaneous substitutions `b[x := t, y := u]`.
 pub enum Pattern<L: Language> {
     ENode(L, Vec<Pattern<L>>),
     PVar(String), // ?x
+    CondPVar(String, SlotCondition), // ?x\{$1}, ?x{$1, $2}, ?x{$1, ..}
+    HOPVar(String, Vec<Slot>), // ?f[$x, $y]
     Subst(Box<Pattern<L>>, Box<Pattern<L>>, Box<Pattern<L>>), // Subst(b, x, t) means `b[x := t]`
+    SubstMany(Box<Pattern<L>>, Vec<(Pattern<L>, Pattern<L>)>), // SubstMany(b, [(x, t), (y, u)]) means `b[x := t, y := u]`
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, Debug)]
+/// A side-condition on the free slots of the e-class matched by a pattern-variable.
+///
+/// The slots are given by their names in the pattern.
+/// The e-matcher checks these conditions as soon as possible, so that it can prune early.
+pub enum SlotCondition {
+    /// `?x\{$1, $2}`: None of these slots are free in `?x`.
+    Excludes(Vec<Slot>),
+
+    /// `?x{$1, $2}`: Only these slots may be free in `?x`.
+    Within(Vec<Slot>),
+
+    /// `?x{$1, $2, ..}`: All of these slots are free in `?x`.
+    Contains(Vec<Slot>),
+}
+
+impl SlotCondition {
+    /// Checks the condition against the slots of a match, given in terms of the pattern slots.
+    pub fn holds(&self, slots: &HashSet<Slot>) -> bool {
+        match self {
+            SlotCondition::Excludes(v) => v.iter().all(|x| !slots.contains(x)),
+            SlotCondition::Within(v) => slots.iter().all(|x| v.contains(x)),
+            SlotCondition::Contains(v) => v.iter().all(|x| slots.contains(x)),
+        }
+    }
+
+    pub(crate) fn slot_list(&self) -> &[Slot] {
+        match self {
+            SlotCondition::Excludes(v) | SlotCondition::Within(v) | SlotCondition::Contains(v) => v,
+        }
+    }
+}
+
+impl<L: Language> Pattern<L> {
+    /// Lists all the [SlotCondition]s in this pattern, together with their pattern-variables.
+    pub fn slot_conditions(&self) -> Vec<(String, SlotCondition)> {
+        let mut out = Vec::new();
+        self.slot_conditions_impl(&mut out);
+        out
+    }
+
+    fn slot_conditions_impl(&self, out: &mut Vec<(String, SlotCondition)>) {
+        match self {
+            Pattern::ENode(_, children) => {
+                for c in children {
+                    c.slot_conditions_impl(out);
+                }
+            },
+            Pattern::PVar(_) | Pattern::HOPVar(..) => {},
+            Pattern::CondPVar(v, cond) => out.push((v.clone(), cond.clone())),
+            Pattern::Subst(..) | Pattern::SubstMany(..) => {
+                let (b, pairs) = self.subst_parts().unwrap();
+                b.slot_conditions_impl(out);
+                for (x, t) in pairs {
+                    x.slot_conditions_impl(out);
+                    t.slot_conditions_impl(out);
+                }
+            },
+        }
+    }
+
+    // Decomposes the substitutions `b[x := t]` and `b[x := t, y := u, ...]` into `b` and their pairs.
+    pub(crate) fn subst_parts(&self) -> Option<SubstParts<'_, L>> {
+        match self {
+            Pattern::Subst(b, x, t) => Some((b, vec![(&**x, &**t)])),
+            Pattern::SubstMany(b, pairs) => Some((b, pairs.iter().map(|(x, t)| (x, t)).collect())),
+            _ => None,
+        }
+    }
+}
+
+// A substituted pattern `b`, and the pairs `(x, t)` substituted into it.
+pub(crate) type SubstParts<'a, L> = (&'a Pattern<L>, Vec<(&'a Pattern<L>, &'a Pattern<L>)>);
+
+impl<L: Language> Pattern<L> {
+    /// Parses a pattern, and checks that it can be used as the lhs of a rewrite rule.
+    pub fn parse_lhs(s: &str) -> Result<Self, RewriteErrorKind> {
+        let pat = Pattern::parse(s).map_err(RewriteErrorKind::LhsParse)?;
+        pat.check_lhs()?;
+        Ok(pat)
+    }
+
+    /// Checks whether the e-matcher supports this pattern.
+    ///
+    /// The only supported substitutions are single renamings `?b\{$y}[(var $x) := (var $y)]`, in which `$y` may not be free in `?b`.
+    /// Such a renaming matches the e-class `i` if `$x` is not free in `i`, and then binds `?b` to `i[$y := $x]`, which is the only preimage of `i`.
+    /// Without the condition, `?b` could also be any e-class in which only some occurences of `$y` are replaced by `$x`,
+    /// which the e-matcher can't enumerate, so such renamings are rejected (unless `$x` and `$y` coincide).
+    pub fn check_lhs(&self) -> Result<(), RewriteErrorKind> {
+        self.var_kinds(&mut HashMap::default())?;
+        self.check_lhs_impl()
+    }
+
+    fn check_lhs_impl(&self) -> Result<(), RewriteErrorKind> {
+        match self {
+            Pattern::ENode(_, children) => children.iter().try_for_each(|c| c.check_lhs_impl()),
+            Pattern::PVar(_) | Pattern::CondPVar(..) | Pattern::HOPVar(..) => Ok(()),
+            Pattern::Subst(b, x, t) => match (renaming_subst(b, x, t), &**b) {
+                (Some((_, x, y)), _) if x == y => Ok(()),
+                (Some((_, _, y)), Pattern::CondPVar(_, SlotCondition::Excludes(v))) if v.contains(&y) => Ok(()),
+                _ => Err(RewriteErrorKind::UnsupportedLhs(self.to_string())),
+            },
+            Pattern::SubstMany(..) => Err(RewriteErrorKind::UnsupportedLhs(self.to_string())),
+        }
+    }
+
+    pub(crate) fn check_not_bare(&self) -> Result<(), RewriteErrorKind> {
+        match self {
+            Pattern::PVar(_) | Pattern::CondPVar(..) | Pattern::HOPVar(..) => Err(RewriteErrorKind::BareLhs),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that this pattern can be used as the rhs of a rewrite rule with the given `lhs`.
+    pub fn check_rhs(&self, lhs: &Pattern<L>) -> Result<(), RewriteErrorKind> {
+        let lhs_vars = lhs.pattern_vars();
+        if let Some(v) = self.pattern_vars().into_iter().find(|v| !lhs_vars.contains(v)) {
+            return Err(RewriteErrorKind::UnboundVar(v));
+        }
+
+        // Each pattern-variable needs the same slot arguments as in the lhs.
+        let mut kinds = HashMap::default();
+        lhs.var_kinds(&mut kinds)?;
+        self.var_kinds(&mut kinds)?;
+
+        let lhs_slots = lhs.free_slots();
+        if let Some(s) = self.free_slots().into_iter().find(|s| !lhs_slots.contains(s)) {
+            return Err(RewriteErrorKind::FreeRhsSlot(s));
+        }
+
+        Ok(())
+    }
+
+    /// The names of all pattern-variables in this pattern.
+    pub fn pattern_vars(&self) -> HashSet<String> {
+        match self {
+            Pattern::ENode(_, children) => children.iter().flat_map(|c| c.pattern_vars()).collect(),
+            Pattern::PVar(v) | Pattern::CondPVar(v, _) | Pattern::HOPVar(v, _) => [v.clone()].into_iter().collect(),
+            Pattern::Subst(..) | Pattern::SubstMany(..) => {
+                let (b, pairs) = self.subst_parts().unwrap();
+                let mut out = b.pattern_vars();
+                for (x, t) in pairs {
+                    out.extend(x.pattern_vars());
+                    out.extend(t.pattern_vars());
+                }
+                out
+            },
+        }
+    }
+
+    // Collects the kind of each pattern-variable: `None` for `?x`, and `Some(n)` for `?f[$x1, ..., $xn]`.
+    // Fails if a pattern-variable is used with different kinds, compared to its entry in `kinds`.
+    fn var_kinds(&self, kinds: &mut HashMap<String, Option<usize>>) -> Result<(), RewriteErrorKind> {
+        let mut insert = |v: &String, k: Option<usize>| match kinds.get(v) {
+            Some(k2) if *k2 != k => Err(RewriteErrorKind::VarKindMismatch(v.clone())),
+            _ => {
+                kinds.insert(v.clone(), k);
+                Ok(())
+            },
+        };
+        match self {
+            Pattern::ENode(_, children) => children.iter().try_for_each(|c| c.var_kinds(kinds)),
+            Pattern::PVar(v) | Pattern::CondPVar(v, _) => insert(v, None),
+            Pattern::HOPVar(v, args) => insert(v, Some(args.len())),
+            Pattern::Subst(..) | Pattern::SubstMany(..) => {
+                let (b, pairs) = self.subst_parts().unwrap();
+                b.var_kinds(kinds)?;
+                for (x, t) in pairs {
+                    x.var_kinds(kinds)?;
+                    t.var_kinds(kinds)?;
+                }
+                Ok(())
+            },
+        }
+    }
+
+    // The slots that are syntactically free in this pattern.
+    // This excludes slots that only occur free in the e-classes matched by pattern-variables.
+    pub(crate) fn free_slots(&self) -> HashSet<Slot> {
+        match self {
+            Pattern::ENode(n, children) => {
+                let private = n.private_slots();
+                let mut out: HashSet<Slot> = n.public_slot_occurences().into_iter().collect();
+                out.extend(children.iter().flat_map(|c| c.free_slots()).filter(|s| !private.contains(s)));
+                out
+            },
+            Pattern::PVar(_) | Pattern::CondPVar(..) => HashSet::default(),
+            Pattern::HOPVar(_, args) => args.iter().copied().collect(),
+            Pattern::Subst(..) | Pattern::SubstMany(..) => {
+                let (b, pairs) = self.subst_parts().unwrap();
+                let x_slots: HashSet<Slot> = pairs.iter().flat_map(|(x, _)| x.free_slots()).collect();
+                let mut out: HashSet<Slot> = b.free_slots().into_iter().filter(|s| !x_slots.contains(s)).collect();
+                out.extend(pairs.iter().flat_map(|(_, t)| t.free_slots()));
+                out
+            },
+        }
+    }
 }
 
 // We write this as pattern[subst] for short.
-#[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
 pub fn pattern_subst<L: Language, N: Analysis<L>>(eg: &mut EGraph<L, N>, pattern: &Pattern<L>, subst: &Subst) -> AppliedId {
+    pattern_subst_justified(eg, pattern, subst, None)
+}
+
+// Like `pattern_subst`, but the unions made by the SubstMethod are justified by `rule`.
+#[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
+pub(crate) fn pattern_subst_justified<L: Language, N: Analysis<L>>(eg: &mut EGraph<L, N>, pattern: &Pattern<L>, subst: &Subst, rule: Option<&str>) -> AppliedId {
     match &pattern {
         Pattern::ENode(n, children) => {
             let mut n = n.clone();
@@ -22,23 +232,33 @@ pub fn pattern_subst<L: Language, N: Analysis<L>>(eg: &mut EGraph<L, N>, pattern
                 assert_eq!(children.len(), refs.len());
             }
             for i in 0..refs.len() {
-                *(refs[i]) = pattern_subst(eg, &children[i], subst);
+                *(refs[i]) = pattern_subst_justified(eg, &children[i], subst, rule);
             }
             eg.add_syn(n)
         },
-        Pattern::PVar(v) => {
+        // The slot conditions only restrict matching, they don't affect instantiation.
+        Pattern::PVar(v) | Pattern::CondPVar(v, _) => {
             subst.get(v)
                  .unwrap_or_else(|| panic!("encountered `?{v}` in pattern, but it is missing in the `subst`"))
                  .clone()
         },
-        Pattern::Subst(b, x, t) => {
-            let b = pattern_subst(eg, &*b, subst);
-            let x = pattern_subst(eg, &*x, subst);
-            let t = pattern_subst(eg, &*t, subst);
+        // The subst stores higher-order matches with their argument slots renamed to `$0, $1, ...`.
+        Pattern::HOPVar(v, args) => {
+            let i = subst.get(v)
+                 .unwrap_or_else(|| panic!("encountered `?{v}` in pattern, but it is missing in the `subst`"));
+            let pairs: Vec<_> = args.iter().enumerate().map(|(j, x)| (Slot::numeric(j as _), *x)).collect();
+            rename_slots(i, &pairs)
+        },
+        Pattern::Subst(..) | Pattern::SubstMany(..) => {
+            let (b, pairs) = pattern.subst_parts().unwrap();
+            let b = pattern_subst_justified(eg, b, subst, rule);
+            let pairs: Vec<_> = pairs.iter()
+                                     .map(|(x, t)| (pattern_subst_justified(eg, x, subst, rule), pattern_subst_justified(eg, t, subst, rule)))
+                                     .collect();
 
             // temporary swap-out so that we can access both the e-graph and the subst-method fully.
             let mut method = eg.subst_method.take().unwrap();
-            let out = method.subst(b, x, t, eg);
+            let out = method.subst_many(b, &pairs, rule, eg);
             eg.subst_method = Some(method);
             out
         },
@@ -0,0 +1,33 @@
+use crate::*;
+
+/// The left-hand side of a [Rewrite]: finds the matches of a rule in the e-graph.
+///
+/// Besides [Pattern], any closure `Fn(&EGraph<L, N>) -> Vec<Subst>` is a Searcher.
+pub trait Searcher<L: Language, N: Analysis<L>> {
+    /// Returns all matches of this Searcher in the e-graph.
+    fn search(&self, eg: &EGraph<L, N>) -> Vec<Subst>;
+
+    /// The [Pattern] describing the matched terms, if there is one.
+    ///
+    /// Appliers use it to instantiate the matched term, so that they can union their result with it.
+    fn pattern(&self) -> Option<&Pattern<L>> { None }
+}
+
+impl<L: Language, N: Analysis<L>> Searcher<L, N> for Pattern<L> {
+    fn search(&self, eg: &EGraph<L, N>) -> Vec<Subst> {
+        ematch_all(eg, self)
+    }
+
+    fn pattern(&self) -> Option<&Pattern<L>> { Some(self) }
+}
+
+impl<L, N, F> Searcher<L, N> for F
+where
+    L: Language,
+    N: Analysis<L>,
+    F: Fn(&EGraph<L, N>) -> Vec<Subst>,
+{
+    fn search(&self, eg: &EGraph<L, N>) -> Vec<Subst> {
+        self(eg)
+    }
+}
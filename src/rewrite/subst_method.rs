@@ -3,7 +3,39 @@ use crate::*;
 /// Specifies a certain implementation of how substitution `b[x := t]` is implemented internally.
 pub trait SubstMethod<L: Language, N: Analysis<L>> {
     fn new_boxed() -> Box<dyn SubstMethod<L, N>> where Self: Sized;
+
+    /// The substitution `b[x := t]`.
     fn subst(&mut self, b: AppliedId, x: AppliedId, t: AppliedId, eg: &mut EGraph<L, N>) -> AppliedId;
+
+    /// The simultaneous substitution `b[x1 := t1, x2 := t2, ...]`.
+    ///
+    /// `_rule` is the name of the rule that instantiates the substitution (if any), which should justify the unions the method makes.
+    ///
+    /// By default, each `xi` is first renamed to a copy `zi` with fresh slots, and then the `zi` are substituted one after another.
+    /// So the `ti` can't interfere with each other, as long as each `xi` has a free slot, like `(var $x)`.
+    fn subst_many(&mut self, b: AppliedId, pairs: &[(AppliedId, AppliedId)], _rule: Option<&str>, eg: &mut EGraph<L, N>) -> AppliedId {
+        if let [(x, t)] = pairs {
+            return self.subst(b, x.clone(), t.clone(), eg);
+        }
+
+        let mut b = b;
+        let mut renamed = Vec::new();
+        for (x, t) in pairs {
+            let z = x.apply_slotmap(&SlotMap::bijection_from_fresh_to(&x.slots()).inverse());
+            b = self.subst(b, x.clone(), z.clone(), eg);
+            renamed.push((z, t.clone()));
+        }
+        for (z, t) in renamed {
+            b = self.subst(b, z, t, eg);
+        }
+        b
+    }
+
+    /// Called before a rule applies its matches, see [Rewrite::apply].
+    ///
+    /// The e-graph might have changed since the last application, so this should drop any caches that depend on its classes.
+    /// The same instance is used for all matches of one application.
+    fn reset(&mut self) {}
 }
 
 /// A [SubstMethod] that uses the [EGraph::get_syn_expr] of an e-class to do substitution on it.
@@ -15,12 +47,19 @@ impl<L: Language, N: Analysis<L>> SubstMethod<L, N> for SynExprSubst {
     }
 
     fn subst(&mut self, b: AppliedId, x: AppliedId, t: AppliedId, eg: &mut EGraph<L, N>) -> AppliedId {
+        self.subst_many(b, &[(x, t)], None, eg)
+    }
+
+    fn subst_many(&mut self, b: AppliedId, pairs: &[(AppliedId, AppliedId)], _rule: Option<&str>, eg: &mut EGraph<L, N>) -> AppliedId {
         let term = eg.get_syn_expr(&eg.synify_app_id(b));
-        do_term_subst(eg, &term, &x, &t)
+        do_term_subst(eg, &term, pairs)
     }
 }
 
 /// A [SubstMethod] that extracts the smallest term (measured by [AstSize]) of an e-class to do substitution on it.
+///
+/// See [AlgorithmSubst] to use another [ExtractionAlgorithm].
+#[derive(Default, Clone)]
 pub struct ExtractionSubst;
 
 impl<L: Language, N: Analysis<L>> SubstMethod<L, N> for ExtractionSubst {
@@ -29,27 +68,211 @@ impl<L: Language, N: Analysis<L>> SubstMethod<L, N> for ExtractionSubst {
     }
 
     fn subst(&mut self, b: AppliedId, x: AppliedId, t: AppliedId, eg: &mut EGraph<L, N>) -> AppliedId {
-        let term = ast_size_extract::<L, N>(&b, eg);
-        do_term_subst(eg, &term, &x, &t)
+        self.subst_many(b, &[(x, t)], None, eg)
+    }
+
+    fn subst_many(&mut self, b: AppliedId, pairs: &[(AppliedId, AppliedId)], rule: Option<&str>, eg: &mut EGraph<L, N>) -> AppliedId {
+        AlgorithmSubst(GreedyExtraction(AstSize)).subst_many(b, pairs, rule, eg)
+    }
+}
+
+/// A [SubstMethod] that extracts a term of an e-class using the [ExtractionAlgorithm] `A`, to do substitution on it.
+///
+/// [SubstMethod::new_boxed] uses the default algorithm of type `A`.
+/// To use a configured algorithm for a rule, pass it with [Rewrite::with_subst_method_instance].
+#[derive(Default, Clone)]
+pub struct AlgorithmSubst<A>(pub A);
+
+impl<L: Language, N: Analysis<L>, A: ExtractionAlgorithm<L, N> + Default + 'static> SubstMethod<L, N> for AlgorithmSubst<A> {
+    fn new_boxed() -> Box<dyn SubstMethod<L, N>> {
+        Box::new(AlgorithmSubst(A::default()))
+    }
+
+    fn subst(&mut self, b: AppliedId, x: AppliedId, t: AppliedId, eg: &mut EGraph<L, N>) -> AppliedId {
+        self.subst_many(b, &[(x, t)], None, eg)
+    }
+
+    fn subst_many(&mut self, b: AppliedId, pairs: &[(AppliedId, AppliedId)], _rule: Option<&str>, eg: &mut EGraph<L, N>) -> AppliedId {
+        let term = self.0.extract(&b, eg);
+        do_term_subst(eg, &term, pairs)
+    }
+}
+
+/// A [SubstMethod] that does substitution natively on the e-graph.
+///
+/// It pushes the substitution `b[x := t]` through every e-node of the e-class `b`, and recursively through its children.
+/// Private slots of the e-nodes (i.e. binders) are renamed to fresh slots, so that they don't capture the free slots of `t`.
+/// All the resulting e-nodes are then unioned together.
+/// E-nodes that lead back to an e-class which is still being substituted (i.e. cycles) are deferred, and substituted once that e-class is done.
+///
+/// The substituted e-classes are cached across the matches of one rule application, until [SubstMethod::reset].
+///
+/// This requires each `x` to have exactly one slot, as for example `(var $x)`. Otherwise, it falls back to [SynExprSubst].
+#[derive(Default)]
+pub struct EGraphSubst {
+    done: HashMap<SubstKey, AppliedId>,
+}
+
+impl<L: Language, N: Analysis<L>> SubstMethod<L, N> for EGraphSubst {
+    fn new_boxed() -> Box<dyn SubstMethod<L, N>> {
+        Box::new(EGraphSubst::default())
+    }
+
+    fn subst(&mut self, b: AppliedId, x: AppliedId, t: AppliedId, eg: &mut EGraph<L, N>) -> AppliedId {
+        self.subst_many(b, &[(x, t)], None, eg)
+    }
+
+    fn subst_many(&mut self, b: AppliedId, pairs: &[(AppliedId, AppliedId)], rule: Option<&str>, eg: &mut EGraph<L, N>) -> AppliedId {
+        if pairs.iter().any(|(x, _)| x.slots().len() != 1) {
+            return SynExprSubst.subst_many(b, pairs, rule, eg);
+        }
+
+        let pairs: Vec<_> = pairs.iter().map(|(x, t)| (eg.find_applied_id(x), eg.find_applied_id(t))).collect();
+
+        let mut st = EGraphSubstState { done: std::mem::take(&mut self.done), ..Default::default() };
+        // There are no in-progress classes at the top-level, so this can't fail.
+        let out = st.subst_class(b, &pairs, eg).unwrap();
+
+        // The e-nodes that ran into a cycle are substituted now, as all the classes they depend on are done.
+        // This might defer further e-nodes, from the classes that are only reached now.
+        while let Some((enode, pairs, key)) = st.deferred.pop() {
+            let r = st.subst_enode(enode, &pairs, eg).unwrap();
+            st.unions.push((st.done[&key].clone(), r));
+        }
+
+        // The unions are deferred, so that the e-graph doesn't change its classes while we traverse it.
+        // They are justified by the rule, but they are not counted as its unions (see `RuleReport::unions`).
+        let union_count = eg.union_count;
+        for (l, r) in st.unions {
+            eg.union_justified(&l, &r, rule.map(str::to_string));
+        }
+        eg.union_count = union_count;
+        self.done = st.done;
+        eg.find_applied_id(&out)
+    }
+
+    fn reset(&mut self) {
+        self.done.clear();
+    }
+}
+
+// The key `(b, [(x, t), ...])` is canonicalized, see `canonicalize`.
+type SubstKey = (AppliedId, SubstPairs);
+type SubstPairs = Vec<(AppliedId, AppliedId)>;
+
+struct EGraphSubstState<L> {
+    done: HashMap<SubstKey, AppliedId>,
+    in_progress: HashSet<SubstKey>,
+    unions: Vec<(AppliedId, AppliedId)>,
+
+    // The e-nodes that depend on a class that was in progress, together with the key of their own class.
+    deferred: Vec<(L, SubstPairs, SubstKey)>,
+}
+
+impl<L> Default for EGraphSubstState<L> {
+    fn default() -> Self {
+        EGraphSubstState { done: HashMap::default(), in_progress: HashSet::default(), unions: Vec::new(), deferred: Vec::new() }
+    }
+}
+
+impl<L: Language> EGraphSubstState<L> {
+    // returns an e-class containing b[x := t, ...], or None if we ran into a cycle.
+    // The e-class might be incomplete until the deferred e-nodes are handled.
+    fn subst_class<N: Analysis<L>>(&mut self, b: AppliedId, pairs: &[(AppliedId, AppliedId)], eg: &mut EGraph<L, N>) -> Option<AppliedId> {
+        let b = eg.find_applied_id(&b);
+
+        // We only need to substitute the variables that occur in `b`.
+        let pairs: Vec<_> = pairs.iter().filter(|(x, _)| x.slots().is_subset(&b.slots())).cloned().collect();
+
+        // trivial substitutions.
+        if pairs.is_empty() { return Some(b); }
+        if let Some((_, t)) = pairs.iter().find(|(x, _)| eg.eq(&b, x)) { return Some(t.clone()); }
+
+        let (key, m) = canonicalize(&b, &pairs);
+        if let Some(out) = self.done.get(&key) {
+            // `out` might come from an earlier match, after which classes were unioned.
+            return Some(eg.find_applied_id(out).apply_slotmap_fresh(&m));
+        }
+
+        // If `b` is already in progress, the calling e-node gets deferred.
+        if !self.in_progress.insert(key.clone()) { return None; }
+
+        let (cb, cpairs) = key.clone();
+        let mut results = Vec::new();
+        for enode in eg.enodes_applied(&cb) {
+            if blocks_subst(&enode, &cpairs) { continue; }
+            match self.subst_enode(enode.clone(), &cpairs, eg) {
+                Some(r) => results.push(r),
+                None => self.deferred.push((enode, cpairs.clone(), key.clone())),
+            }
+        }
+
+        let out = match results.split_first() {
+            Some((first, rest)) => {
+                for r in rest {
+                    self.unions.push((first.clone(), r.clone()));
+                }
+                first.clone()
+            },
+            // Every e-node of `b` was deferred or blocked, but the syntactic term of `b` is acyclic.
+            None => {
+                let term = eg.get_syn_expr(&eg.synify_app_id(cb));
+                do_term_subst(eg, &term, &cpairs)
+            },
+        };
+
+        self.in_progress.remove(&key);
+        self.done.insert(key, out.clone());
+        Some(out.apply_slotmap_fresh(&m))
+    }
+
+    // returns None if a child ran into a cycle.
+    fn subst_enode<N: Analysis<L>>(&mut self, enode: L, pairs: &[(AppliedId, AppliedId)], eg: &mut EGraph<L, N>) -> Option<AppliedId> {
+        let mut enode = enode.refresh_private();
+        for c in enode.applied_id_occurences_mut() {
+            *c = self.subst_class(c.clone(), pairs, eg)?;
+        }
+        Some(eg.add(enode))
+    }
+}
+
+// If some `x` occurs directly in the e-node (and not through a child), we can't substitute it by an e-class.
+fn blocks_subst<L: Language>(enode: &L, pairs: &[(AppliedId, AppliedId)]) -> bool {
+    let x_slots: HashSet<Slot> = pairs.iter().flat_map(|(x, _)| x.slots()).collect();
+    let child_slots: HashSet<Slot> = enode.applied_id_occurences().iter().flat_map(|c| c.slots()).collect();
+    enode.public_slot_occurences().iter().any(|s| x_slots.contains(s) && !child_slots.contains(s))
+}
+
+// Renames the slots of `(b, [(x, t), ...])` to `$0, $1, ...`, so that equivalent substitutions get the same key.
+// Returns the renamed key, and the slotmap undoing the renaming.
+fn canonicalize(b: &AppliedId, pairs: &[(AppliedId, AppliedId)]) -> (SubstKey, SlotMap) {
+    let mut m = SlotMap::new();
+    for a in std::iter::once(b).chain(pairs.iter().flat_map(|(x, t)| [x, t])) {
+        for (_, s) in a.m.iter() {
+            if !m.contains_key(s) {
+                m.insert(s, Slot::numeric(m.len() as u32));
+            }
+        }
     }
+    let pairs = pairs.iter().map(|(x, t)| (x.apply_slotmap(&m), t.apply_slotmap(&m))).collect();
+    ((b.apply_slotmap(&m), pairs), m.inverse())
 }
 
-// returns re[x := t]
-fn do_term_subst<L: Language, N: Analysis<L>>(eg: &mut EGraph<L, N>, re: &RecExpr<L>, x: &AppliedId, t: &AppliedId) -> AppliedId {
+// returns re[x := t, ...]
+fn do_term_subst<L: Language, N: Analysis<L>>(eg: &mut EGraph<L, N>, re: &RecExpr<L>, pairs: &[(AppliedId, AppliedId)]) -> AppliedId {
     let mut n = re.node.clone();
     let mut refs: Vec<&mut AppliedId> = n.applied_id_occurences_mut();
     if CHECKS {
         assert_eq!(re.children.len(), refs.len());
     }
     for i in 0..refs.len() {
-        *(refs[i]) = do_term_subst(eg, &re.children[i], x, t);
+        *(refs[i]) = do_term_subst(eg, &re.children[i], pairs);
     }
     let app_id = eg.add_syn(n);
 
-    if app_id == *x {
-        return t.clone();
-    } else {
-        app_id
+    match pairs.iter().find(|(x, _)| app_id == *x) {
+        Some((_, t)) => t.clone(),
+        None => app_id,
     }
 }
 
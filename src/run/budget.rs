@@ -0,0 +1,133 @@
+use crate::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+/// A handle to cancel equality saturation, possibly from another thread.
+///
+/// All clones of a CancellationToken share the same state.
+#[derive(Clone, Default, Debug)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests cancellation. Running operations stop at the next budget check.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+type MemoryUsage = Arc<dyn Fn() -> usize + Send + Sync>;
+
+/// Limits that interrupt e-matching and rewrite application on an e-graph, see [EGraph::set_budget].
+///
+/// The budget is checked for every e-class during e-matching, and for every match during application.
+/// A union always completes its rebuild before the budget is checked again, so an interrupted e-graph is left in a consistent, rebuilt state.
+/// The rebuild itself is never interrupted, as a partially rebuilt e-graph would break the congruence invariants that e-matching relies on.
+#[derive(Clone, Default)]
+pub struct Budget {
+    tokens: Vec<CancellationToken>,
+    node_limit: Option<usize>,
+    deadline: Option<Instant>,
+    memory_limits: Vec<(usize, MemoryUsage)>,
+}
+
+impl Budget {
+    /// Creates a budget without any limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops with [StopReason::Cancelled] once `token` (or any other token of this budget) is cancelled.
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.tokens.push(token);
+        self
+    }
+
+    /// Stops with [StopReason::NodeLimit] once the e-graph has more than `limit` e-nodes.
+    pub fn with_node_limit(mut self, limit: usize) -> Self {
+        self.node_limit = Some(limit);
+        self
+    }
+
+    /// Stops with [StopReason::TimeLimit] once `deadline` has passed.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Stops with [StopReason::MemoryLimit] once `usage()` exceeds `limit`.
+    ///
+    /// The memory usage is not tracked by the e-graph, so `usage` should query it e.g. from your allocator.
+    pub fn with_memory_limit(mut self, limit: usize, usage: impl Fn() -> usize + Send + Sync + 'static) -> Self {
+        self.memory_limits.push((limit, Arc::new(usage)));
+        self
+    }
+
+    // The budget that is exceeded as soon as `self` or `other` is exceeded.
+    pub(crate) fn merge(mut self, other: &Budget) -> Budget {
+        fn min<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            }
+        }
+
+        self.tokens.extend(other.tokens.iter().cloned());
+        self.node_limit = min(self.node_limit, other.node_limit);
+        self.deadline = min(self.deadline, other.deadline);
+        self.memory_limits.extend(other.memory_limits.iter().cloned());
+        self
+    }
+
+    fn check<L: Language, N: Analysis<L>>(&self, eg: &EGraph<L, N>) -> Option<StopReason> {
+        if self.tokens.iter().any(|t| t.is_cancelled()) {
+            return Some(StopReason::Cancelled);
+        }
+
+        if self.node_limit.is_some_and(|l| eg.total_number_of_nodes() > l) {
+            return Some(StopReason::NodeLimit);
+        }
+
+        if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            return Some(StopReason::TimeLimit);
+        }
+
+        if self.memory_limits.iter().any(|(limit, usage)| usage() > *limit) {
+            return Some(StopReason::MemoryLimit);
+        }
+
+        None
+    }
+}
+
+impl<L: Language, N: Analysis<L>> EGraph<L, N> {
+    /// Sets the [Budget] of this e-graph, and resets whether it was exceeded.
+    pub fn set_budget(&mut self, budget: Budget) {
+        self.budget = budget;
+        *self.budget_exceeded.get_mut() = None;
+    }
+
+    /// Checks the [Budget] of this e-graph, and returns true if it is exceeded.
+    ///
+    /// Once exceeded, the budget stays exceeded until [EGraph::set_budget] is called.
+    /// Custom [Searcher]s and [Applier]s should call this regularly, and stop early if it returns true.
+    pub fn check_budget(&self) -> bool {
+        let mut exceeded = self.budget_exceeded.borrow_mut();
+        if exceeded.is_none() {
+            *exceeded = self.budget.check(self);
+        }
+        exceeded.is_some()
+    }
+
+    /// Why the [Budget] was exceeded, if it was.
+    pub fn budget_exceeded(&self) -> Option<StopReason> {
+        self.budget_exceeded.borrow().clone()
+    }
+}
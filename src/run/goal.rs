@@ -0,0 +1,145 @@
+use crate::*;
+
+/// A Goal lets a [Runner] stop as soon as the e-graph has established some fact.
+///
+/// Every goal is of the form "the term `t` is equal to some term `w`".
+/// The witness `(t, w)` is used to explain the goal, when the `explanations` feature is enabled.
+pub trait Goal<L: Language, N: Analysis<L>> {
+    /// Adds the terms of this goal to the e-graph.
+    ///
+    /// This is called at the start of every [Runner::run], before the goal is checked.
+    fn init(&mut self, eg: &mut EGraph<L, N>);
+
+    /// Returns the witness `(t, w)` if the goal is reached, and `None` otherwise.
+    ///
+    /// This is called on a rebuilt e-graph, before the first and after every iteration.
+    fn check(&mut self, eg: &EGraph<L, N>) -> Option<(RecExpr<L>, RecExpr<L>)>;
+}
+
+/// The [Goal] that two terms are equal.
+pub struct EqualGoal<L: Language> {
+    l: RecExpr<L>,
+    r: RecExpr<L>,
+
+    // The e-classes of `l` and `r`, once the goal is initialized.
+    ids: Option<(AppliedId, AppliedId)>,
+}
+
+impl<L: Language> EqualGoal<L> {
+    pub fn new(l: &RecExpr<L>, r: &RecExpr<L>) -> Self {
+        EqualGoal { l: l.clone(), r: r.clone(), ids: None }
+    }
+}
+
+impl<L: Language, N: Analysis<L>> Goal<L, N> for EqualGoal<L> {
+    fn init(&mut self, eg: &mut EGraph<L, N>) {
+        let l = eg.add_syn_expr(self.l.clone());
+        let r = eg.add_syn_expr(self.r.clone());
+        self.ids = Some((l, r));
+    }
+
+    fn check(&mut self, eg: &EGraph<L, N>) -> Option<(RecExpr<L>, RecExpr<L>)> {
+        let (l, r) = self.ids.as_ref().expect("EqualGoal: checked before `init`!");
+        let (l, r) = (eg.find_applied_id(l), eg.find_applied_id(r));
+        eg.eq(&l, &r).then(|| (self.l.clone(), self.r.clone()))
+    }
+}
+
+/// The [Goal] that the e-class of a term contains a term matching a [Pattern].
+///
+/// The matched term has to use the same free slots as the original term.
+pub struct PatternGoal<L: Language> {
+    term: RecExpr<L>,
+    pattern: Pattern<L>,
+
+    // The e-class of `term`, once the goal is initialized.
+    id: Option<AppliedId>,
+}
+
+impl<L: Language> PatternGoal<L> {
+    pub fn new(term: &RecExpr<L>, pattern: &Pattern<L>) -> Self {
+        PatternGoal { term: term.clone(), pattern: pattern.clone(), id: None }
+    }
+}
+
+impl<L: Language, N: Analysis<L>> Goal<L, N> for PatternGoal<L> {
+    fn init(&mut self, eg: &mut EGraph<L, N>) {
+        self.id = Some(eg.add_syn_expr(self.term.clone()));
+    }
+
+    fn check(&mut self, eg: &EGraph<L, N>) -> Option<(RecExpr<L>, RecExpr<L>)> {
+        let i = self.id.as_ref().expect("PatternGoal: checked before `init`!");
+        let i = eg.find_applied_id(i);
+        ematch_class(eg, &self.pattern, &i).into_iter()
+            .find_map(|subst| instantiate(&self.pattern, &subst, eg))
+            .map(|w| (self.term.clone(), w))
+    }
+}
+
+// Builds the term `pattern[subst]`, without adding it to the e-graph.
+// Returns None for a renaming `b[x := y]`, if both `x` and `y` are free in `b`.
+fn instantiate<L: Language, N: Analysis<L>>(pattern: &Pattern<L>, subst: &Subst, eg: &EGraph<L, N>) -> Option<RecExpr<L>> {
+    let syn = |i: AppliedId| eg.get_syn_expr(&eg.synify_app_id(i));
+    match pattern {
+        Pattern::ENode(n, children) => {
+            let children = children.iter().map(|c| instantiate(c, subst, eg)).collect::<Option<_>>()?;
+            Some(RecExpr { node: n.clone(), children })
+        },
+        Pattern::PVar(v) | Pattern::CondPVar(v, _) => Some(syn(subst[v].clone())),
+        Pattern::HOPVar(v, args) => {
+            let pairs: Vec<_> = args.iter().enumerate().map(|(j, x)| (Slot::numeric(j as _), *x)).collect();
+            Some(syn(rename_slots(&subst[v], &pairs)))
+        },
+        Pattern::Subst(b, x, t) => {
+            let (v, x, y) = renaming_subst(b, x, t)?;
+            let i = &subst[&v];
+            if i.slots().contains(&x) && i.slots().contains(&y) { return None; }
+            Some(syn(rename_slots(i, &[(x, y)])))
+        },
+        Pattern::SubstMany(..) => None,
+    }
+}
+
+/// The [Goal] that a term is equal to a term of cost at most `max_cost`.
+///
+/// The [Extractor] is kept between the checks, and only refreshed for the e-classes that changed, see [Extractor::refresh].
+pub struct CostGoal<L: Language, CF: CostFunction<L>> {
+    term: RecExpr<L>,
+    cost_fn: CF,
+    max_cost: CF::Cost,
+
+    // The e-class of `term`, once the goal is initialized.
+    id: Option<AppliedId>,
+    extractor: Option<Extractor<L, CF>>,
+}
+
+impl<L: Language, CF: CostFunction<L>> CostGoal<L, CF> {
+    pub fn new(term: &RecExpr<L>, cost_fn: CF, max_cost: CF::Cost) -> Self {
+        CostGoal { term: term.clone(), cost_fn, max_cost, id: None, extractor: None }
+    }
+}
+
+impl<L: Language, N: Analysis<L>, CF: CostFunction<L> + Clone> Goal<L, N> for CostGoal<L, CF> {
+    fn init(&mut self, eg: &mut EGraph<L, N>) {
+        self.id = Some(eg.add_syn_expr(self.term.clone()));
+
+        // The e-graph might have been replaced since the last run.
+        self.extractor = None;
+    }
+
+    fn check(&mut self, eg: &EGraph<L, N>) -> Option<(RecExpr<L>, RecExpr<L>)> {
+        let i = self.id.as_ref().expect("CostGoal: checked before `init`!");
+        let i = eg.find_applied_id(i);
+        let extractor = match &mut self.extractor {
+            Some(extractor) => {
+                extractor.refresh(eg);
+                extractor
+            },
+            None => self.extractor.insert(Extractor::new(eg, self.cost_fn.clone())),
+        };
+        if extractor.get_best_cost(&i, eg) > self.max_cost {
+            return None;
+        }
+        Some((self.term.clone(), extractor.extract(&i, eg)))
+    }
+}
@@ -1,7 +1,16 @@
 use crate::*;
 
+mod budget;
+pub use budget::*;
+
+mod goal;
+pub use goal::*;
+
 mod report;
 pub use report::*;
 
 mod run;
-pub use run::*;
\ No newline at end of file
+pub use run::*;
+
+mod scheduler;
+pub use scheduler::*;
\ No newline at end of file
@@ -1,14 +1,202 @@
+use crate::*;
+use std::fmt::{self, Display, Formatter, Write};
+use std::time::Duration;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// Why a [Runner](crate::Runner) stopped.
 pub enum StopReason {
+    /// The rewrites didn't change the e-graph anymore.
     Saturated,
+
+    /// The e-graph exceeded the e-node limit.
+    NodeLimit,
+
+    /// The e-graph exceeded the e-class limit.
+    ClassLimit,
+
+    /// The iteration limit was reached.
     IterationLimit,
+
+    /// The time limit was reached.
     TimeLimit,
+
+    /// The memory limit of the [Budget](crate::Budget) was exceeded.
+    MemoryLimit,
+
+    /// The [CancellationToken](crate::CancellationToken) was cancelled.
+    Cancelled,
+
+    /// A [Goal](crate::Goal) was reached.
+    GoalReached,
+
+    /// A hook returned an error.
     Other(String),
 }
 
+/// What happened in a single iteration of a [Runner](crate::Runner).
+#[derive(Clone, Debug)]
+pub struct Iteration {
+    /// The number of e-nodes after this iteration.
+    pub egraph_nodes: usize,
+
+    /// The number of e-classes after this iteration.
+    pub egraph_classes: usize,
+
+    /// The [ProgressMeasure] after this iteration.
+    pub progress: ProgressMeasure,
+
+    /// Whether the rewrites changed the e-graph in this iteration.
+    pub changed: bool,
+
+    /// The statistics of each rule that was searched in this iteration.
+    pub rules: Vec<RuleReport>,
+
+    /// How long it took to search the rewrites.
+    pub search_time: Duration,
+
+    /// How long it took to apply the rewrites, excluding the rebuild time.
+    pub apply_time: Duration,
+
+    /// How long it took to rebuild the e-graph.
+    pub rebuild_time: Duration,
+
+    /// How long it took to run the hooks.
+    pub hook_time: Duration,
+}
+
+/// What a single rule did, either in one [Iteration] or in the whole run, see [Report::rule_totals].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RuleReport {
+    /// The name of the rule.
+    pub name: String,
+
+    /// How many matches were found, and passed on to be applied.
+    pub matches: usize,
+
+    /// How many of the applied matches changed the e-graph.
+    ///
+    /// Only the unions justified by the rule are counted (see [EGraph::union_justified]), like the union of the lhs with the rhs.
+    /// Unions made while building the rhs, e.g. by the [EGraphSubst], don't count.
+    pub unions: usize,
+
+    pub search_time: Duration,
+    pub apply_time: Duration,
+    pub rebuild_time: Duration,
+}
+
+/// A summary of a run of a [Runner](crate::Runner).
+#[derive(Clone, Debug)]
 pub struct Report {
-    pub iterations: usize,
+    pub iterations: Vec<Iteration>,
     pub stop_reason: StopReason,
     pub egraph_nodes: usize,
     pub egraph_classes: usize,
-    pub total_time: f64,
-}
\ No newline at end of file
+    pub total_time: Duration,
+}
+
+impl RuleReport {
+    fn add(&mut self, other: &RuleReport) {
+        self.matches += other.matches;
+        self.unions += other.unions;
+        self.search_time += other.search_time;
+        self.apply_time += other.apply_time;
+        self.rebuild_time += other.rebuild_time;
+    }
+}
+
+impl Report {
+    /// Sums up the statistics of each rule over all iterations, in the order the rules were first searched.
+    pub fn rule_totals(&self) -> Vec<RuleReport> {
+        let mut out: Vec<RuleReport> = Vec::new();
+        for r in self.iterations.iter().flat_map(|it| &it.rules) {
+            match out.iter_mut().find(|x| x.name == r.name) {
+                Some(x) => x.add(r),
+                None => out.push(r.clone()),
+            }
+        }
+        out
+    }
+
+    /// Exports the report as CSV, with one row per iteration and rule.
+    ///
+    /// Times are given in seconds.
+    pub fn to_csv(&self) -> String {
+        let mut s = String::from("iteration,rule,matches,unions,search_time,apply_time,rebuild_time,egraph_nodes,egraph_classes,number_of_classes,number_of_live_classes,sum_of_slots,sum_of_symmetries\n");
+        for (i, it) in self.iterations.iter().enumerate() {
+            let p = &it.progress;
+            let it_cols = format!("{},{},{},{},{},{}", it.egraph_nodes, it.egraph_classes, p.number_of_classes, p.number_of_live_classes, p.sum_of_slots, p.sum_of_symmetries);
+            if it.rules.is_empty() {
+                writeln!(s, "{i},,,,,,,{it_cols}").unwrap();
+            }
+            for r in &it.rules {
+                writeln!(s, "{i},{},{},{},{},{},{},{it_cols}", csv_field(&r.name), r.matches, r.unions, r.search_time.as_secs_f64(), r.apply_time.as_secs_f64(), r.rebuild_time.as_secs_f64()).unwrap();
+            }
+        }
+        s
+    }
+
+    /// Exports the report as JSON.
+    ///
+    /// Times are given in seconds.
+    pub fn to_json(&self) -> String {
+        let iterations: Vec<String> = self.iterations.iter().map(|it| {
+            let p = &it.progress;
+            let rules: Vec<String> = it.rules.iter().map(|r| {
+                format!("{{\"name\":{},\"matches\":{},\"unions\":{},\"search_time\":{},\"apply_time\":{},\"rebuild_time\":{}}}",
+                    json_string(&r.name), r.matches, r.unions, r.search_time.as_secs_f64(), r.apply_time.as_secs_f64(), r.rebuild_time.as_secs_f64())
+            }).collect();
+            format!("{{\"egraph_nodes\":{},\"egraph_classes\":{},\"changed\":{},\"progress\":{{\"number_of_classes\":{},\"number_of_live_classes\":{},\"sum_of_slots\":{},\"sum_of_symmetries\":{}}},\"search_time\":{},\"apply_time\":{},\"rebuild_time\":{},\"hook_time\":{},\"rules\":[{}]}}",
+                it.egraph_nodes, it.egraph_classes, it.changed,
+                p.number_of_classes, p.number_of_live_classes, p.sum_of_slots, p.sum_of_symmetries,
+                it.search_time.as_secs_f64(), it.apply_time.as_secs_f64(), it.rebuild_time.as_secs_f64(), it.hook_time.as_secs_f64(),
+                rules.join(","))
+        }).collect();
+
+        format!("{{\"stop_reason\":{},\"egraph_nodes\":{},\"egraph_classes\":{},\"total_time\":{},\"iterations\":[{}]}}",
+            json_string(&format!("{:?}", self.stop_reason)), self.egraph_nodes, self.egraph_classes, self.total_time.as_secs_f64(), iterations.join(","))
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Runner report")?;
+        writeln!(f, "=============")?;
+        writeln!(f, "  Stop reason: {:?}", self.stop_reason)?;
+        writeln!(f, "  Iterations: {}", self.iterations.len())?;
+        writeln!(f, "  E-nodes: {}", self.egraph_nodes)?;
+        writeln!(f, "  E-classes: {}", self.egraph_classes)?;
+        writeln!(f, "  Total time: {:?}", self.total_time)?;
+
+        let totals = self.rule_totals();
+        if !totals.is_empty() {
+            writeln!(f, "  Rules:")?;
+        }
+        for r in totals {
+            writeln!(f, "    {}: {} matches, {} unions, search {:?}, apply {:?}, rebuild {:?}", r.name, r.matches, r.unions, r.search_time, r.apply_time, r.rebuild_time)?;
+        }
+        Ok(())
+    }
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -1,50 +1,329 @@
 use crate::*;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-// TODO: Turn this into a nicer interface like egg's `Runner`.
+type Hook<'a, L, N> = Box<dyn FnMut(&mut EGraph<L, N>) -> Result<(), String> + 'a>;
 
+/// Runs equality saturation on `egraph`, for at most `iter_limit` iterations and `time_limit` seconds.
+///
+/// The `hook` is called after every iteration.
+/// Unlike the [Runner], this applies `iter_limit + 1` rounds of rewrites before it stops with [StopReason::IterationLimit],
+/// and it checks the time limit only between the rounds.
+///
+/// The returned [Report] lists every round in [Report::iterations], and measures the `total_time` as a [Duration].
+#[deprecated(note = "use the `Runner` instead. The `Report` now lists the iterations instead of counting them, and `total_time` is a `Duration`")]
 pub fn run_eqsat<L: Language, N: Analysis<L>, F>(
-    egraph: &mut EGraph<L, N>, 
-    rws: Vec<Rewrite<L, N>>, 
-    iter_limit: usize, 
-    time_limit: usize, 
+    egraph: &mut EGraph<L, N>,
+    rws: Vec<Rewrite<L, N>>,
+    iter_limit: usize,
+    time_limit: usize,
     mut hook: F
 ) -> Report where F: FnMut(&mut EGraph<L, N>) -> Result<(), String> + 'static {
     let start_time = Instant::now();
-    let mut iterations = 0;
-    let stop_reason: StopReason;
+    let mut iterations = Vec::new();
 
-    loop {
-        let did_change = apply_rewrites(egraph, &rws);
-        
-        match hook(egraph) {
-            Ok(_) => (),
-            Err(msg) => { stop_reason = StopReason::Other(msg.to_string()); break }
+    let stop_reason = loop {
+        let mut rules = Vec::new();
+        let changed = apply_rewrites_impl(egraph, &rws, &mut SimpleScheduler, iterations.len(), &mut rules);
+
+        let hook_start = Instant::now();
+        let hook_result = hook(egraph);
+        iterations.push(record_iteration(egraph, changed, rules, hook_start.elapsed()));
+
+        if let Err(msg) = hook_result {
+            break StopReason::Other(msg);
         }
 
-        if !did_change {
-            stop_reason = StopReason::Saturated;
-            break
+        if !changed {
+            break StopReason::Saturated;
         }
 
-        if iterations >= iter_limit {
-            stop_reason = StopReason::IterationLimit;
-            break
+        if iterations.len() > iter_limit {
+            break StopReason::IterationLimit;
         }
 
-        if start_time.elapsed().as_secs() >= time_limit.try_into().unwrap() {
-            stop_reason = StopReason::TimeLimit;
-            break
+        if start_time.elapsed() >= Duration::from_secs(time_limit as u64) {
+            break StopReason::TimeLimit;
         }
-        
-        iterations += 1;
-    }
+    };
 
     Report {
         iterations,
         stop_reason,
         egraph_nodes: egraph.total_number_of_nodes(),
         egraph_classes: egraph.ids().len(),
-        total_time: start_time.elapsed().as_secs_f64()
+        total_time: start_time.elapsed(),
+    }
+}
+
+// The record of an iteration that just ran.
+fn record_iteration<L: Language, N: Analysis<L>>(egraph: &EGraph<L, N>, changed: bool, rules: Vec<RuleReport>, hook_time: Duration) -> Iteration {
+    Iteration {
+        egraph_nodes: egraph.total_number_of_nodes(),
+        egraph_classes: egraph.ids().len(),
+        progress: egraph.progress(),
+        changed,
+        search_time: rules.iter().map(|r| r.search_time).sum(),
+        apply_time: rules.iter().map(|r| r.apply_time).sum(),
+        rebuild_time: rules.iter().map(|r| r.rebuild_time).sum(),
+        rules,
+        hook_time,
+    }
+}
+
+/// Runs equality saturation on an e-graph.
+///
+/// The Runner is configured using the builder pattern, and then started with [Runner::run].
+pub struct Runner<'a, L: Language, N: Analysis<L> = ()> {
+    /// The e-graph that equality saturation runs on.
+    pub egraph: EGraph<L, N>,
+
+    /// The e-classes of the expressions added using [Runner::with_expr].
+    pub roots: Vec<AppliedId>,
+
+    /// A record for each iteration that has been run.
+    pub iterations: Vec<Iteration>,
+
+    /// Why the Runner stopped, if it has been run.
+    pub stop_reason: Option<StopReason>,
+
+    /// An explanation of the goal, if the Runner stopped with [StopReason::GoalReached].
+    #[cfg(feature = "explanations")]
+    pub explanation: Option<ProvenEq>,
+
+    iter_limit: usize,
+    node_limit: usize,
+    class_limit: usize,
+    time_limit: Duration,
+    budget: Budget,
+    total_time: Duration,
+
+    hooks: Vec<Hook<'a, L, N>>,
+    goals: Vec<Box<dyn Goal<L, N> + 'a>>,
+    scheduler: Box<dyn Scheduler<L, N> + 'a>,
+}
+
+impl<'a, L: Language, N: Analysis<L>> Default for Runner<'a, L, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, L: Language, N: Analysis<L>> Runner<'a, L, N> {
+    /// Creates a Runner with an empty e-graph and the default limits.
+    ///
+    /// The defaults are 30 iterations, 10,000 e-nodes, unlimited e-classes and 5 seconds.
+    pub fn new() -> Self {
+        Runner {
+            egraph: EGraph::new(),
+            roots: Vec::new(),
+            iterations: Vec::new(),
+            stop_reason: None,
+            #[cfg(feature = "explanations")]
+            explanation: None,
+            iter_limit: 30,
+            node_limit: 10_000,
+            class_limit: usize::MAX,
+            time_limit: Duration::from_secs(5),
+            budget: Budget::new(),
+            total_time: Duration::ZERO,
+            hooks: Vec::new(),
+            goals: Vec::new(),
+            scheduler: Box::new(SimpleScheduler),
+        }
+    }
+
+    /// Runs on the given e-graph instead of an empty one.
+    ///
+    /// The [Runner::roots] point into the replaced e-graph, so they are cleared.
+    /// Call this before [Runner::with_expr].
+    pub fn with_egraph(mut self, egraph: EGraph<L, N>) -> Self {
+        self.egraph = egraph;
+        self.roots.clear();
+        self
+    }
+
+    /// Adds an expression to the e-graph, and adds its e-class to the [Runner::roots].
+    pub fn with_expr(mut self, re: &RecExpr<L>) -> Self {
+        let i = self.egraph.add_syn_expr(re.clone());
+        self.roots.push(i);
+        self
+    }
+
+    /// Sets the maximal number of iterations.
+    pub fn with_iter_limit(mut self, limit: usize) -> Self {
+        self.iter_limit = limit;
+        self
+    }
+
+    /// Sets the maximal number of e-nodes.
+    ///
+    /// Like the time limit, it is also checked during an iteration, see [Budget].
+    pub fn with_node_limit(mut self, limit: usize) -> Self {
+        self.node_limit = limit;
+        self
+    }
+
+    /// Sets the maximal number of e-classes.
+    pub fn with_class_limit(mut self, limit: usize) -> Self {
+        self.class_limit = limit;
+        self
+    }
+
+    /// Sets the maximal running time.
+    ///
+    /// The time limit is also checked during an iteration, see [Budget].
+    pub fn with_time_limit(mut self, limit: Duration) -> Self {
+        self.time_limit = limit;
+        self
+    }
+
+    /// Stops with [StopReason::Cancelled] once `token` is cancelled, even during an iteration.
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.budget = self.budget.with_cancellation_token(token);
+        self
+    }
+
+    /// Stops with [StopReason::MemoryLimit] once `usage()` exceeds `limit`, even during an iteration.
+    ///
+    /// See [Budget::with_memory_limit].
+    pub fn with_memory_limit(mut self, limit: usize, usage: impl Fn() -> usize + Send + Sync + 'static) -> Self {
+        self.budget = self.budget.with_memory_limit(limit, usage);
+        self
+    }
+
+    /// Adds a hook that is called after every iteration.
+    ///
+    /// If the hook returns an error, the Runner stops with [StopReason::Other].
+    pub fn with_hook(mut self, hook: impl FnMut(&mut EGraph<L, N>) -> Result<(), String> + 'a) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Adds a [Goal]. The Runner stops with [StopReason::GoalReached] once any of its goals is reached.
+    pub fn with_goal(mut self, goal: impl Goal<L, N> + 'a) -> Self {
+        self.goals.push(Box::new(goal));
+        self
+    }
+
+    /// Uses the given [Scheduler], the default is the [SimpleScheduler].
+    pub fn with_scheduler(mut self, scheduler: impl Scheduler<L, N> + 'a) -> Self {
+        self.scheduler = Box::new(scheduler);
+        self
+    }
+
+    /// Runs equality saturation with the given rewrites, until one of the limits is hit or the e-graph is saturated.
+    ///
+    /// If the run is interrupted during an iteration, the e-graph is still left in a consistent, rebuilt state.
+    ///
+    /// The limits of the Runner are combined with the [Budget] of the e-graph (see [EGraph::set_budget]):
+    /// the run stops once either is exceeded. Afterwards, the e-graph gets back its own budget.
+    pub fn run(mut self, rewrites: &[Rewrite<L, N>]) -> Self {
+        let start_time = Instant::now();
+
+        for goal in &mut self.goals {
+            goal.init(&mut self.egraph);
+        }
+
+        let mut budget = self.budget.clone().with_node_limit(self.node_limit);
+        if let Some(deadline) = start_time.checked_add(self.time_limit) {
+            budget = budget.with_deadline(deadline);
+        }
+
+        // The budget of the e-graph is only extended during the run.
+        let old_budget = std::mem::take(&mut self.egraph.budget);
+        self.egraph.set_budget(budget.merge(&old_budget));
+
+        let stop_reason = loop {
+            if self.check_goals() {
+                break StopReason::GoalReached;
+            }
+
+            if let Some(reason) = self.check_limits() {
+                break reason;
+            }
+
+            let i = self.iterations.len();
+
+            let mut rules = Vec::new();
+            let changed = apply_rewrites_impl(&mut self.egraph, rewrites, &mut *self.scheduler, i, &mut rules);
+
+            // The hooks are skipped, if the budget was exceeded during this iteration.
+            let interrupted = self.egraph.budget_exceeded();
+
+            let hook_start = Instant::now();
+            let hook_result = match interrupted {
+                Some(_) => Ok(()),
+                None => self.hooks.iter_mut().try_for_each(|hook| hook(&mut self.egraph)),
+            };
+            let hook_time = hook_start.elapsed();
+
+            self.iterations.push(record_iteration(&self.egraph, changed, rules, hook_time));
+
+            if let Some(reason) = interrupted {
+                break reason;
+            }
+
+            if let Err(msg) = hook_result {
+                break StopReason::Other(msg);
+            }
+
+            if !changed && self.scheduler.can_stop(i) {
+                break StopReason::Saturated;
+            }
+        };
+
+        self.egraph.set_budget(old_budget);
+        self.stop_reason = Some(stop_reason);
+        self.total_time += start_time.elapsed();
+        self
+    }
+
+    /// Runs equality saturation until `t1` and `t2` are equal, or until one of the limits is hit.
+    pub fn run_until_equal(self, t1: &RecExpr<L>, t2: &RecExpr<L>, rewrites: &[Rewrite<L, N>]) -> Self where L: 'a {
+        self.with_expr(t1)
+            .with_expr(t2)
+            .with_goal(EqualGoal::new(t1, t2))
+            .run(rewrites)
+    }
+
+    fn check_goals(&mut self) -> bool {
+        for goal in &mut self.goals {
+            if let Some((_t, _w)) = goal.check(&self.egraph) {
+                #[cfg(feature = "explanations")]
+                {
+                    self.explanation = Some(self.egraph.explain_equivalence(_t, _w));
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    fn check_limits(&self) -> Option<StopReason> {
+        if self.iterations.len() >= self.iter_limit {
+            return Some(StopReason::IterationLimit);
+        }
+
+        if self.egraph.ids().len() > self.class_limit {
+            return Some(StopReason::ClassLimit);
+        }
+
+        // The node limit, time limit, memory limit & cancellation.
+        if self.egraph.check_budget() {
+            return self.egraph.budget_exceeded();
+        }
+
+        None
+    }
+
+    /// Summarizes the run.
+    pub fn report(&self) -> Report {
+        Report {
+            iterations: self.iterations.clone(),
+            stop_reason: self.stop_reason.clone().unwrap_or_else(|| StopReason::Other("not run yet".to_string())),
+            egraph_nodes: self.egraph.total_number_of_nodes(),
+            egraph_classes: self.egraph.ids().len(),
+            total_time: self.total_time,
+        }
     }
 }
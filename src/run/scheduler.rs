@@ -0,0 +1,186 @@
+use crate::*;
+use std::time::Instant;
+
+/// A Scheduler decides which rules are searched & applied in each iteration of equality saturation.
+///
+/// The default implementations of the methods simply search & apply every rule.
+///
+/// A rule is identified by its `index` in the slice of rules, as the names of rules need not be unique.
+pub trait Scheduler<L: Language, N: Analysis<L>> {
+    /// Whether the equality saturation is allowed to stop, after an iteration that didn't change the e-graph.
+    ///
+    /// A scheduler that skipped some rules should return false here, and give these rules another chance.
+    fn can_stop(&mut self, _iteration: usize) -> bool {
+        true
+    }
+
+    /// Finds the matches of `rw` that should be applied in this iteration.
+    fn search_rewrite(&mut self, _iteration: usize, eg: &EGraph<L, N>, _index: usize, rw: &Rewrite<L, N>) -> Matches<L, N> {
+        rw.search(eg)
+    }
+
+    /// Applies the `matches` of `rw`, as returned by [Scheduler::search_rewrite].
+    fn apply_rewrite(&mut self, _iteration: usize, eg: &mut EGraph<L, N>, _index: usize, rw: &Rewrite<L, N>, matches: Matches<L, N>) {
+        rw.apply(matches, eg);
+    }
+}
+
+/// A [Scheduler] that searches & applies every rule in every iteration.
+#[derive(Default)]
+pub struct SimpleScheduler;
+
+impl<L: Language, N: Analysis<L>> Scheduler<L, N> for SimpleScheduler {}
+
+/// A [Scheduler] that temporarily bans rules that find too many matches, as in egg.
+///
+/// A rule is banned, if it finds more than `match_limit << times_banned` matches.
+/// It then stays banned for `ban_length << times_banned` iterations.
+/// So rules that keep exploding the e-graph get banned for exponentially longer.
+///
+/// The bans are tracked per rule index, so rules with the same name are banned independently.
+pub struct BackoffScheduler {
+    default_match_limit: usize,
+    default_ban_length: usize,
+
+    // The initial match limit & ban length of the rules with the given name.
+    rule_limits: HashMap<String, (usize, usize)>,
+
+    // The stats by the index of the rule.
+    stats: HashMap<usize, RuleStats>,
+}
+
+struct RuleStats {
+    times_banned: usize,
+    banned_until: usize,
+    match_limit: usize,
+    ban_length: usize,
+}
+
+impl Default for BackoffScheduler {
+    fn default() -> Self {
+        BackoffScheduler {
+            default_match_limit: 1_000,
+            default_ban_length: 5,
+            rule_limits: HashMap::default(),
+            stats: HashMap::default(),
+        }
+    }
+}
+
+impl BackoffScheduler {
+    /// Sets the initial match limit for all rules.
+    pub fn with_initial_match_limit(mut self, limit: usize) -> Self {
+        self.default_match_limit = limit;
+        self
+    }
+
+    /// Sets the initial ban length for all rules.
+    pub fn with_ban_length(mut self, ban_length: usize) -> Self {
+        self.default_ban_length = ban_length;
+        self
+    }
+
+    /// Sets the initial match limit & ban length for the rules called `rule`.
+    pub fn rule_match_limit(mut self, rule: &str, limit: usize, ban_length: usize) -> Self {
+        self.rule_limits.insert(rule.to_string(), (limit, ban_length));
+        self
+    }
+
+    /// Never bans the rules called `rule`.
+    pub fn do_not_ban(self, rule: &str) -> Self {
+        self.rule_match_limit(rule, usize::MAX, 0)
+    }
+
+    fn rule_stats(&mut self, index: usize, rule: &str) -> &mut RuleStats {
+        let (match_limit, ban_length) = self.rule_limits.get(rule).copied()
+            .unwrap_or((self.default_match_limit, self.default_ban_length));
+        self.stats.entry(index).or_insert_with(|| RuleStats {
+            times_banned: 0,
+            banned_until: 0,
+            match_limit,
+            ban_length,
+        })
+    }
+}
+
+impl<L: Language, N: Analysis<L>> Scheduler<L, N> for BackoffScheduler {
+    fn can_stop(&mut self, iteration: usize) -> bool {
+        // If some rules are banned, we unban them and try again.
+        let mut banned = false;
+        for s in self.stats.values_mut() {
+            if s.banned_until > iteration {
+                s.banned_until = iteration;
+                banned = true;
+            }
+        }
+        !banned
+    }
+
+    fn search_rewrite(&mut self, iteration: usize, eg: &EGraph<L, N>, index: usize, rw: &Rewrite<L, N>) -> Matches<L, N> {
+        let s = self.rule_stats(index, rw.name());
+        if iteration < s.banned_until {
+            return Matches::none();
+        }
+
+        let matches = rw.search(eg);
+        let threshold = s.match_limit.checked_shl(s.times_banned as u32).unwrap_or(usize::MAX);
+        if matches.len() > threshold {
+            let ban_length = s.ban_length.checked_shl(s.times_banned as u32).unwrap_or(usize::MAX);
+            s.times_banned += 1;
+            s.banned_until = iteration.saturating_add(ban_length);
+            return Matches::none();
+        }
+
+        matches
+    }
+}
+
+/// Applies the given rewrite rules to the E-Graph once, as decided by the [Scheduler].
+///
+/// The rules are applied in phases by their [Rewrite::priority], starting with the highest priority.
+/// A phase is only reached, if all previous phases didn't change the e-graph.
+/// Returns an indicator for whether the e-graph changed as a result.
+#[cfg_attr(feature = "trace", instrument(level = "trace", skip_all))]
+pub fn apply_rewrites_with<L: Language, N: Analysis<L>>(eg: &mut EGraph<L, N>, rewrites: &[Rewrite<L, N>], scheduler: &mut (impl Scheduler<L, N> + ?Sized), iteration: usize) -> bool {
+    apply_rewrites_impl(eg, rewrites, scheduler, iteration, &mut Vec::new())
+}
+
+// Like `apply_rewrites_with`, but additionally pushes a `RuleReport` for each searched rule.
+pub(crate) fn apply_rewrites_impl<L: Language, N: Analysis<L>>(eg: &mut EGraph<L, N>, rewrites: &[Rewrite<L, N>], scheduler: &mut (impl Scheduler<L, N> + ?Sized), iteration: usize, reports: &mut Vec<RuleReport>) -> bool {
+    let mut priorities: Vec<i32> = rewrites.iter().map(|rw| rw.priority()).collect();
+    priorities.sort_unstable_by(|a, b| b.cmp(a));
+    priorities.dedup();
+
+    for p in priorities {
+        let prog = eg.modification_count();
+
+        let phase: Vec<(usize, &Rewrite<L, N>)> = rewrites.iter().enumerate().filter(|(_, rw)| rw.priority() == p).collect();
+        let offset = reports.len();
+        let mut ts: Vec<Matches<L, N>> = Vec::new();
+        for &(index, rw) in &phase {
+            if eg.check_budget() { break; }
+            let start = Instant::now();
+            let t = scheduler.search_rewrite(iteration, eg, index, rw);
+            reports.push(RuleReport {
+                name: rw.name().to_string(),
+                matches: t.len(),
+                search_time: start.elapsed(),
+                ..RuleReport::default()
+            });
+            ts.push(t);
+        }
+
+        for (((index, rw), t), r) in phase.into_iter().zip(ts).zip(&mut reports[offset..]) {
+            let (unions, rebuild_time) = (eg.union_count, eg.rebuild_time);
+            let start = Instant::now();
+            scheduler.apply_rewrite(iteration, eg, index, rw, t);
+            r.unions = eg.union_count - unions;
+            r.rebuild_time = eg.rebuild_time - rebuild_time;
+            r.apply_time = start.elapsed().saturating_sub(r.rebuild_time);
+        }
+
+        if prog != eg.modification_count() { return true; }
+    }
+
+    false
+}
@@ -62,3 +62,59 @@ fn const_prop_union() {
 
     assert_eq!(eg.analysis_data(a.id), &ConstProp(Some(42)));
 }
+
+#[test]
+fn modification_count() {
+    let mut eg = EGraph::<Arith, ConstProp>::new();
+    let x = eg.add_expr(RecExpr::parse("(add a 1)").unwrap());
+    let a = eg.add_expr(RecExpr::parse("a").unwrap());
+    let b = eg.add_expr(RecExpr::parse("41").unwrap());
+    let n = eg.modification_count();
+
+    // Adding existing terms, and re-establishing known equations doesn't change the e-graph.
+    eg.add_expr(RecExpr::parse("(add a 1)").unwrap());
+    assert!(!eg.union(&x, &x));
+    assert_eq!(eg.modification_count(), n);
+
+    // The union also updates the analysis data of `(add a 1)`.
+    assert!(eg.union(&a, &b));
+    assert_eq!(eg.analysis_data(x.id), &ConstProp(Some(42)));
+    let n2 = eg.modification_count();
+    assert!(n2 > n);
+
+    assert!(!eg.union(&a, &b));
+    assert_eq!(eg.modification_count(), n2);
+}
+
+// Adding two non-constants is expensive.
+struct ConstAdd;
+
+impl ContextCostFunction<Arith, ConstProp> for ConstAdd {
+    type Cost = u32;
+
+    fn context_cost<C>(&self, ctx: &CostContext<'_, Arith, ConstProp>, costs: C) -> u32 where C: Fn(usize) -> u32 {
+        let children = ctx.enode.applied_id_occurences();
+        let is_const = |x: &AppliedId| ctx.egraph.is_some_and(|eg| eg.analysis_data(x.id).0.is_some());
+        let own = match ctx.enode {
+            Arith::Add(..) if !children.iter().any(is_const) => 10,
+            _ => 1,
+        };
+        own + (0..children.len()).map(costs).sum::<u32>()
+    }
+}
+
+#[test]
+fn context_cost_analysis() {
+    let mut eg = EGraph::<Arith, ConstProp>::new();
+    let a = eg.add_expr(RecExpr::parse("(add (var $0) (var $0))").unwrap());
+    let b = eg.add_expr(RecExpr::parse("(mul (var $0) (add 1 1))").unwrap());
+    eg.union(&a, &b);
+    let a = eg.find_applied_id(&a);
+
+    // A plain cost function doesn't need the analysis in the type of the extractor.
+    let extractor: Extractor<Arith, AstSize> = Extractor::new(&eg, AstSize);
+    assert_eq!(extractor.extract(&a, &eg).to_string(), "(add (var $0) (var $0))");
+    let extractor = Extractor::new(&eg, ConstAdd);
+    assert_eq!(extractor.extract(&a, &eg).to_string(), "(mul (var $0) (add 1 1))");
+    assert_eq!(extractor.get_best_cost(&a, &eg), 5);
+}
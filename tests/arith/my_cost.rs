@@ -17,3 +17,15 @@ impl CostFunction<Arith> for AstSizeNoLet {
         }
     }
 }
+
+/// The AST size, plus the number of free slots of every subterm.
+pub struct FreeSlots;
+
+impl ContextCostFunction<Arith, ()> for FreeSlots {
+    type Cost = usize;
+
+    fn context_cost<C>(&self, ctx: &CostContext<'_, Arith, ()>, costs: C) -> usize where C: Fn(usize) -> usize {
+        let children = ctx.enode.applied_id_occurences().len();
+        1 + ctx.slots.len() + (0..children).map(costs).sum::<usize>()
+    }
+}
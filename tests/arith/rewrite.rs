@@ -31,26 +31,22 @@ fn beta() -> Rewrite<Arith> {
 }
 
 fn eta() -> Rewrite<Arith> {
-    let pat = "(lam $1 (app ?b (var $1)))";
+    let pat = "(lam $1 (app ?b\\{$1} (var $1)))";
     let outpat = "?b";
 
-    Rewrite::new_if("eta", pat, outpat, |subst, _| {
-        !subst["b"].slots().contains(&Slot::numeric(1))
-    })
+    Rewrite::new("eta", pat, outpat)
 }
 
 fn eta_expansion() -> Rewrite<Arith> {
     let pat = "?b";
     let outpat = "(lam $1 (app ?b (var $1)))";
-    Rewrite::new("eta-expansion", pat, outpat)
+    Rewrite::new_bare("eta-expansion", pat, outpat)
 }
 
 fn my_let_unused() -> Rewrite<Arith> {
-    let pat = "(let $1 ?t ?b)";
+    let pat = "(let $1 ?t ?b\\{$1})";
     let outpat = "?b";
-    Rewrite::new_if("my-let-unused", pat, outpat, |subst, _| {
-        !subst["b"].slots().contains(&Slot::numeric(1))
-    })
+    Rewrite::new("my-let-unused", pat, outpat)
 }
 
 fn let_var_same() -> Rewrite<Arith> {
@@ -68,11 +64,9 @@ fn let_app() -> Rewrite<Arith> {
 }
 
 fn let_lam_diff() -> Rewrite<Arith> {
-    let pat = "(let $1 ?e (lam $2 ?b))";
+    let pat = "(let $1 ?e (lam $2 ?b{$1, ..}))";
     let outpat = "(lam $2 (let $1 ?e ?b))";
-    Rewrite::new_if("let-lam-diff", pat, outpat, |subst, _| {
-        subst["b"].slots().contains(&Slot::numeric(1))
-    })
+    Rewrite::new("let-lam-diff", pat, outpat)
 }
 
 pub fn add_comm() -> Rewrite<Arith> {
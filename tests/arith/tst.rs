@@ -67,7 +67,7 @@ fn t4() { // (x+y)**2 = x**2 + x*y + x*y + y**2
     assert_reaches(a, b, 10);
 }
 
-fn add_chain(it: impl Iterator<Item=usize>) -> String {
+pub fn add_chain(it: impl Iterator<Item=usize>) -> String {
     let mut it = it.map(|u| format!("(var ${u})"));
     let mut x = format!("{}", it.next().unwrap());
     for y in it {
@@ -36,6 +36,8 @@ fn translate(p: Pattern<Sym>) -> Pattern<Array> {
             }
         }
         Pattern::PVar(x) => Pattern::PVar(x),
-        Pattern::Subst(..) => panic!(),
+        Pattern::CondPVar(x, cond) => Pattern::CondPVar(x, cond),
+        Pattern::HOPVar(x, args) => Pattern::HOPVar(x, args),
+        Pattern::Subst(..) | Pattern::SubstMany(..) => panic!(),
     }
 }
@@ -51,20 +51,16 @@ fn beta() -> Rewrite<Array> {
 }
 
 fn eta() -> Rewrite<Array> {
-    let pat = "(lam $1 (app ?f (var $1)))";
+    let pat = "(lam $1 (app ?f\\{$1} (var $1)))";
     let outpat = "?f";
 
-    Rewrite::new_if("eta", pat, outpat, |subst, _| {
-        !subst["f"].slots().contains(&Slot::numeric(1))
-    })
+    Rewrite::new("eta", pat, outpat)
 }
 
 fn my_let_unused() -> Rewrite<Array> {
-    let pat = "(let $1 ?t ?b)";
+    let pat = "(let $1 ?t ?b\\{$1})";
     let outpat = "?b";
-    Rewrite::new_if("my-let-unused", pat, outpat, |subst, _| {
-        !subst["b"].slots().contains(&Slot::numeric(1))
-    })
+    Rewrite::new("my-let-unused", pat, outpat)
 }
 
 fn let_var_same() -> Rewrite<Array> {
@@ -88,11 +84,9 @@ fn let_app() -> Rewrite<Array> {
 }
 
 fn let_lam_diff() -> Rewrite<Array> {
-    let pat = "(let $1 ?e (lam $2 ?body))";
+    let pat = "(let $1 ?e (lam $2 ?body{$1, ..}))";
     let outpat = "(lam $2 (let $1 ?e ?body))";
-    Rewrite::new_if("let-lam-diff", pat, outpat, |subst, _| {
-        subst["body"].slots().contains(&Slot::numeric(1))
-    })
+    Rewrite::new("let-lam-diff", pat, outpat)
 }
 
 /////////////////////
@@ -109,14 +103,12 @@ fn map_fission() -> Rewrite<Array> {
     let mfi = 1;
 
     let pat = &format!(
-        "(app (app m ?nn) (lam ${x} (app ?f ?gx)))"
+        "(app (app m ?nn) (lam ${x} (app ?f\\{{${x}}} ?gx)))"
     );
 
     let outpat = &format!(
         "(lam ${mfi} (app (app (app m ?nn) ?f) (app (app (app m ?nn) (lam ${x} ?gx)) (var ${mfi}))))"
     );
 
-    Rewrite::new_if("map-fission", pat, outpat, move |subst, _| {
-        !subst["f"].slots().contains(&Slot::numeric(x))
-    })
+    Rewrite::new("map-fission", pat, outpat)
 }
@@ -30,6 +30,12 @@ pub use fgh::*;
 mod sdql;
 pub use sdql::*;
 
+mod extraction;
+
+mod rewrite;
+
+mod run;
+
 pub fn singleton_set<T: Eq + Hash>(t: T) -> HashSet<T> {
     [t].into_iter().collect()
 }
@@ -43,6 +49,15 @@ pub fn id<L: Language>(s: &str, eg: &mut EGraph<L>) -> AppliedId {
     out
 }
 
+// Like `id`, but adds the term with `EGraph::add_expr`, so it is not kept syntactically.
+pub fn add_term<L: Language>(s: &str, eg: &mut EGraph<L>) -> AppliedId {
+    eg.add_expr(RecExpr::parse(s).unwrap())
+}
+
+pub fn lookup<L: Language>(s: &str, eg: &EGraph<L>) -> Option<AppliedId> {
+    lookup_rec_expr(&RecExpr::parse(s).unwrap(), eg)
+}
+
 pub fn term<L: Language>(s: &str, eg: &mut EGraph<L>) -> RecExpr<L> {
     let re = RecExpr::parse(s).unwrap();
     re
@@ -0,0 +1,2 @@
+// The tests of the extractors, using the `Arith` language.
+mod tst;
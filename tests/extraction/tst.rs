@@ -0,0 +1,293 @@
+use crate::*;
+
+#[test]
+fn dag_extraction() {
+    let re = RecExpr::parse("(add (mul (var $0) (var $1)) (mul (var $0) (var $1)))").unwrap();
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let i = eg.add_expr(re.clone());
+    let dag = DagExtractor::new(&eg, AstSize).extract(&i, &eg);
+    assert_eq!(dag.cost, 4);
+    assert_eq!(dag.nodes.len(), 4);
+    assert_eq!(dag.nodes[3].children(), &[2, 2]);
+    assert!(matches!(dag.nodes[3].enode_with(|k| Id(k * 10)), Arith::Add(a, b) if a.id == Id(20) && b.id == Id(20)));
+    assert_eq!(dag.to_rec_expr().to_string(), re.to_string());
+
+    // The same e-class under different slot renamings is not shared.
+    let re = RecExpr::parse("(add (mul (var $0) (var $1)) (mul (var $1) (var $0)))").unwrap();
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let i = eg.add_expr(re.clone());
+    let dag = DagExtractor::new(&eg, AstSize).extract(&i, &eg);
+    assert_eq!(dag.cost, 5);
+    assert_eq!(dag.to_rec_expr().to_string(), re.to_string());
+
+    // `x+x` is cheaper than `x*2` as a DAG, but not as a tree.
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let a = add_term("(mul (var $0) 2)", &mut eg);
+    let b = add_term("(add (var $0) (var $0))", &mut eg);
+    eg.union(&a, &b);
+    let dag = DagExtractor::new(&eg, AstSize).extract(&a, &eg);
+    assert_eq!(dag.cost, 2);
+    assert_eq!(dag.to_rec_expr().to_string(), "(add (var $0) (var $0))");
+}
+
+#[test]
+fn term_enumeration() {
+    // `x = x*1` makes the e-graph cyclic, so there are infinitely many terms.
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let a = add_term("(var $0)", &mut eg);
+    let b = add_term("(mul (var $0) 1)", &mut eg);
+    eg.union(&a, &b);
+
+    let terms: Vec<_> = TermEnumerator::new(&a, &eg, AstSize).take(3).map(|(t, c)| (t.to_string(), c)).collect();
+    assert_eq!(terms, vec![
+        ("(var $0)".to_string(), 1),
+        ("(mul (var $0) 1)".to_string(), 3),
+        ("(mul (mul (var $0) 1) 1)".to_string(), 5),
+    ]);
+    assert_eq!(TermEnumerator::new(&a, &eg, AstSize).with_max_cost(6).count(), 3);
+
+    // The symmetry `a+b = b+a` of the lambda bodies is enumerated, and each binder gets its own private slot.
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let i = add_term("(add (lam $1 (add (var $1) (var $0))) (lam $2 (add (var $2) (var $0))))", &mut eg);
+    apply_rewrites(&mut eg, &[add_comm()]);
+    let terms: Vec<_> = TermEnumerator::new(&i, &eg, AstSize).with_max_cost(9).map(|(t, _)| t).collect();
+    assert_eq!(terms.len(), 4);
+    for t in &terms {
+        let Arith::Lam(x, _) = t.children[0].node else { panic!() };
+        let Arith::Lam(y, _) = t.children[1].node else { panic!() };
+        assert_ne!(x, y);
+    }
+    let strings: HashSet<String> = terms.iter().map(|t| t.to_string()).collect();
+    assert_eq!(strings.len(), 4);
+}
+
+#[test]
+fn context_cost() {
+    assert_eq!(FreeSlots.context_cost_rec(&RecExpr::parse("(lam $0 (var $0))").unwrap()), 3);
+    assert_eq!(FreeSlots.context_cost_rec(&RecExpr::parse("(lam $0 (var $1))").unwrap()), 4);
+    // The first `$0` is free, as the let only binds it in the body.
+    assert_eq!(FreeSlots.context_cost_rec(&RecExpr::parse("(let $0 (var $0) (var $0))").unwrap()), 6);
+
+    // `x*0` is worse than `0`, because of its free slot.
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let a = add_term("(add (mul (var $0) 0) (lam $1 (var $1)))", &mut eg);
+    let b = add_term("(mul (var $0) 0)", &mut eg);
+    let c = add_term("0", &mut eg);
+    eg.union(&b, &c);
+    let extractor = Extractor::new(&eg, FreeSlots);
+    let out = extractor.extract(&a, &eg);
+    assert_eq!(out.children[0].to_string(), "0");
+    assert_eq!(extractor.get_best_cost(&a, &eg), 5);
+    assert_eq!(FreeSlots.context_cost_rec(&out), 5);
+}
+
+#[test]
+fn try_extract() {
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let a = add_term("(mul (var $0) 1)", &mut eg);
+    let extractor = Extractor::new(&eg, AstSize);
+    assert!(extractor.unextractable_classes(&eg).is_empty());
+
+    // An e-class added after the extractor was created has no term.
+    let b = add_term("(add (var $0) 1)", &mut eg);
+    assert_eq!(extractor.try_extract(&b, &eg), None);
+    assert_eq!(extractor.try_best_cost(&b, &eg), None);
+    assert_eq!(extractor.unextractable_classes(&eg), vec![eg.find_applied_id(&b).id]);
+
+    // Ids are canonicalized.
+    let x = add_term("(var $0)", &mut eg);
+    eg.union(&a, &x);
+    let extractor = Extractor::new(&eg, AstSize);
+    assert_eq!(extractor.try_best_cost(&a, &eg), Some(1));
+    assert_eq!(extractor.get_best_cost(&a, &eg), 1);
+    assert_eq!(extractor.try_extract(&a, &eg).unwrap().to_string(), "(var $0)");
+}
+
+#[test]
+fn incremental_extraction() {
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let a = add_term("(add (mul (var $0) 0) (add (var $1) (mul 2 (var $2))))", &mut eg);
+    let mut extractor = Extractor::new(&eg, AstSize);
+
+    let assert_fresh = |extractor: &Extractor<Arith, AstSize>, eg: &EGraph<Arith>| {
+        let fresh = Extractor::new(eg, AstSize);
+        for i in eg.ids() {
+            let i = AppliedId::new(i, SlotMap::new());
+            assert_eq!(extractor.try_best_cost(&i, eg), fresh.try_best_cost(&i, eg));
+        }
+        let out = extractor.extract(&a, eg);
+        assert_eq!(AstSize.cost_rec(&out), fresh.get_best_cost(&a, eg));
+    };
+
+    // A union that shrinks the slots of `x*0`.
+    let b = add_term("(mul (var $0) 0)", &mut eg);
+    let zero = add_term("0", &mut eg);
+    eg.union(&b, &zero);
+    extractor.refresh(&eg);
+    assert_fresh(&extractor, &eg);
+    assert_eq!(extractor.get_best_cost(&a, &eg), 7);
+
+    // Rewriting adds e-nodes to, and merges previously extracted e-classes.
+    apply_rewrites(&mut eg, &[add_comm()]);
+    let c = add_term("(mul 2 (var $2))", &mut eg);
+    let d = add_term("(add (var $2) (var $2))", &mut eg);
+    eg.union(&c, &d);
+    extractor.refresh(&eg);
+    assert_fresh(&extractor, &eg);
+
+    // Nothing changed.
+    extractor.refresh(&eg);
+    assert_fresh(&extractor, &eg);
+}
+
+// The number of distinct subterms of `re`.
+fn dag_size(re: &RecExpr<Arith>) -> usize {
+    fn rec(re: &RecExpr<Arith>, out: &mut HashSet<String>) {
+        out.insert(re.to_string());
+        for c in &re.children {
+            rec(c, out);
+        }
+    }
+    let mut out = HashSet::default();
+    rec(re, &mut out);
+    out.len()
+}
+
+#[test]
+fn extraction_algorithms() {
+    let s = "(add (mul (var $0) (var $1)) 5)";
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let i = add_term(&format!("(add (mul (add (var $0) (var $1)) 6) (app {s} {s}))"), &mut eg);
+
+    // `(lam $2 s)` is more expensive on its own, but it shares `s` with the other child.
+    let a = add_term("(mul (add (var $0) (var $1)) 6)", &mut eg);
+    let b = add_term(&format!("(lam $2 {s})"), &mut eg);
+    eg.union(&a, &b);
+    let i = eg.find_applied_id(&i);
+
+    let greedy = GreedyExtraction(AstSize).extract(&i, &eg);
+    assert_eq!(AstSize.cost_rec(&greedy), 17);
+    assert_eq!(DagExtractor::new(&eg, AstSize).extract(&i, &eg).cost, 10);
+
+    let local = LocalSearchExtraction::new(AstSize).extract(&i, &eg);
+    assert_eq!(dag_size(&local), 8);
+    let j = eg.add_expr(local);
+    assert!(eg.eq(&i, &j));
+
+    let local = LocalSearchExtraction::new(AstSize).with_max_rounds(0).extract(&i, &eg);
+    assert_eq!(dag_size(&local), 10);
+}
+
+#[test]
+fn extract_many() {
+    let s = "(add (mul (var $0) (var $1)) 5)";
+    let s2 = "(add (mul (var $7) (var $8)) 5)";
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let a = add_term("(mul (add (var $0) (var $1)) 6)", &mut eg);
+    let b = add_term(&format!("(lam $2 {s})"), &mut eg);
+    eg.union(&a, &b);
+    let r1 = add_term(&format!("(app {s} {s})"), &mut eg);
+    let r2 = add_term(&format!("(app {s2} {s2})"), &mut eg);
+
+    // Extracted separately, `a` doesn't share `s` with `r1`.
+    let (_, dag) = DagExtractor::new(&eg, AstSize).extract_shared(&[a.clone(), r1.clone(), r2.clone()], &eg);
+    assert_eq!(dag.cost, 14);
+
+    let (terms, dag) = LocalSearchExtraction::new(AstSize).extract_many(&[a.clone(), r1.clone(), r2.clone()], &eg);
+    assert_eq!(dag.cost, 12);
+    assert_eq!(dag.roots.len(), 3);
+    assert!(matches!(terms[0].node, Arith::Lam(..)));
+    assert_eq!(terms[1].to_string(), format!("(app {s} {s})"));
+
+    // `r2` is in the same e-class as `r1`, but with other slots.
+    assert_eq!(r1.id, r2.id);
+    assert_eq!(terms[2].to_string(), format!("(app {s2} {s2})"));
+    assert_eq!(dag.to_rec_exprs(), terms);
+
+    // With multiple roots, the last root is unfolded, even if it is not the last node.
+    let c = add_term(s, &mut eg);
+    let (_, dag) = DagExtractor::new(&eg, AstSize).extract_shared(&[r1.clone(), c], &eg);
+    assert_eq!(dag.roots[1], 4);
+    assert_eq!(dag.to_rec_expr().to_string(), s);
+}
+
+#[test]
+fn constrained_extraction() {
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let a = add_term("(mul (var $0) 2)", &mut eg);
+    let b = add_term("(add (var $0) (add (var $0) 0))", &mut eg);
+    eg.union(&a, &b);
+
+    let extract = |eg: &EGraph<Arith>, constraints: Constraints<Arith>| {
+        ConstrainedExtractor::new(eg, AstSize, constraints).extract(&a, eg).map(|x| x.to_string())
+    };
+    let no_mul = || Constraints::new().forbid(|x| matches!(x, Arith::Mul(..)));
+    let add = "(add (var $0) (add (var $0) 0))".to_string();
+
+    assert_eq!(extract(&eg, Constraints::new()), Ok("(mul (var $0) 2)".to_string()));
+    assert_eq!(extract(&eg, no_mul()), Ok(add.clone()));
+    assert_eq!(extract(&eg, no_mul().with_max_depth(3)), Ok(add.clone()));
+    assert_eq!(extract(&eg, no_mul().with_max_depth(2)), Err(ConstraintError::Unsatisfiable));
+    assert_eq!(extract(&eg, Constraints::new().forbid_pattern(Pattern::parse("(mul ?x 2)").unwrap())), Ok(add.clone()));
+    assert_eq!(extract(&eg, Constraints::new().require_pattern(Pattern::parse("(add ?x 0)").unwrap())), Ok(add.clone()));
+
+    // There are only finitely many terms, and none of them adds a term to itself.
+    let double = || Constraints::new().require_pattern(Pattern::parse("(add ?x ?x)").unwrap());
+    assert_eq!(extract(&eg, double()), Err(ConstraintError::Unsatisfiable));
+
+    // With a cycle, there are infinitely many terms.
+    let c = add_term("(add (mul (var $0) 2) 0)", &mut eg);
+    eg.union(&a, &c);
+    assert_eq!(extract(&eg, double().with_max_candidates(50)), Err(ConstraintError::CandidateLimit(50)));
+    assert_eq!(extract(&eg, double().with_max_depth(4)), Err(ConstraintError::Unsatisfiable));
+}
+
+#[cfg(feature = "sample")]
+#[test]
+fn term_sampling() {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let a = add_term("(var $0)", &mut eg);
+    for s in ["(mul (var $0) 1)", "(add (var $0) 0)"] {
+        let b = add_term(s, &mut eg);
+        eg.union(&a, &b);
+    }
+
+    // The terms of depth at most 3 are `(var $0)`, and `(mul x 1)` and `(add x 0)` for the three terms `x` of depth at most 2.
+    let sampler = TermSampler::new(&eg, 3);
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut counts: HashMap<String, usize> = HashMap::default();
+    for _ in 0..7000 {
+        *counts.entry(sampler.sample(&a, &mut rng).unwrap().to_string()).or_default() += 1;
+    }
+    assert_eq!(counts.len(), 7);
+    assert!(counts.values().all(|c| (800..1200).contains(c)));
+
+    let samples = |seed| {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..10).map(|_| sampler.sample(&a, &mut rng).unwrap().to_string()).collect::<Vec<_>>()
+    };
+    assert_eq!(samples(1), samples(1));
+
+    let sampler = TermSampler::weighted(&eg, 3, |x| if let Arith::Mul(..) = x { 0.0 } else { 1.0 });
+    for _ in 0..100 {
+        assert!(!sampler.sample(&a, &mut rng).unwrap().to_string().contains("mul"));
+    }
+    assert!(TermSampler::new(&eg, 0).sample(&a, &mut rng).is_none());
+
+    // Every sampled binder gets fresh slots, and the sampled terms are in the e-class.
+    let l = add_term("(lam $1 (mul (var $1) (var $0)))", &mut eg);
+    let l2 = add_term("(lam $1 (add (var $0) (var $1)))", &mut eg);
+    eg.union(&l, &l2);
+    let sampler = TermSampler::new(&eg, 3);
+    let terms: Vec<RecExpr<Arith>> = (0..10).map(|_| sampler.sample(&l, &mut rng).unwrap()).collect();
+    let mut binders = HashSet::default();
+    for t in terms {
+        let Arith::Lam(x, _) = t.node else { panic!() };
+        assert!(binders.insert(x));
+        let i = eg.add_expr(t);
+        assert!(eg.eq(&i, &l));
+    }
+}
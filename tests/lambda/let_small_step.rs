@@ -28,11 +28,9 @@ fn beta() -> Rewrite<Lambda> {
 }
 
 fn my_let_unused() -> Rewrite<Lambda> {
-    let pat = "(let $1 ?t ?b)";
+    let pat = "(let $1 ?t ?b\\{$1})";
     let outpat = "?b";
-    Rewrite::new_if("my-let-unused", pat, outpat, |subst, _| {
-        !subst["b"].slots().contains(&Slot::numeric(1))
-    })
+    Rewrite::new("my-let-unused", pat, outpat)
 }
 
 fn let_var_same() -> Rewrite<Lambda> {
@@ -50,9 +48,7 @@ fn let_app() -> Rewrite<Lambda> {
 }
 
 fn let_lam_diff() -> Rewrite<Lambda> {
-    let pat = "(let $1 ?e (lam $2 ?b))";
+    let pat = "(let $1 ?e (lam $2 ?b{$1, ..}))";
     let outpat = "(lam $2 (let $1 ?e ?b))";
-    Rewrite::new_if("let-lam-diff", pat, outpat, |subst, _| {
-        subst["b"].slots().contains(&Slot::numeric(1))
-    })
+    Rewrite::new("let-lam-diff", pat, outpat)
 }
@@ -149,3 +149,110 @@ macro_rules! unpack_tests {
         }
     }
 }
+
+#[test]
+fn let_extraction() {
+    let s = y();
+    let out = extract_lets(&s);
+
+    // The shared `(lam $0 ...)` is bound by a let inside of `(lam $1 ...)`, as it uses $1.
+    let Lambda::Lam(_, _) = out.node else { panic!() };
+    let Lambda::Let(..) = out.children[0].node else { panic!() };
+    assert_eq!(count_lets(&out), 1);
+
+    assert_inlines_to(&out, &s);
+}
+
+#[test]
+fn let_extraction_at_root() {
+    let s = "(app (lam $0 (app (var $0) (var $0))) (lam $1 (app (var $1) (var $1))))";
+    let out = extract_lets(s);
+
+    let Lambda::Let(..) = out.node else { panic!() };
+    let Lambda::App(..) = out.children[1].node else { panic!() };
+    assert_eq!(count_lets(&out), 1);
+
+    assert_inlines_to(&out, s);
+}
+
+#[test]
+fn let_extraction_around_binder() {
+    // The shared `(lam $1 ...)` is used in both children of the let binding $0, but doesn't use $0.
+    let s = "(let $0 (lam $1 (app (var $1) (var $1))) (app (var $0) (lam $2 (app (var $2) (var $2)))))";
+    let out = extract_lets(s);
+
+    // So it gets a single let around the binder.
+    let Lambda::Let(..) = out.node else { panic!() };
+    let Lambda::Lam(..) = out.children[0].node else { panic!() };
+    let Lambda::Let(..) = out.children[1].node else { panic!() };
+    let Lambda::Var(_) = out.children[1].children[0].node else { panic!() };
+    assert_eq!(count_lets(&out), 2);
+
+    assert_inlines_to(&out, s);
+}
+
+#[test]
+fn let_extraction_below_binder() {
+    // The shared `(app (var $0) (var $1))` uses the $0 bound by the let, so its let goes into the body.
+    let s = "(lam $1 (let $0 (var $1) (app (app (var $0) (var $1)) (app (var $0) (var $1)))))";
+    let out = extract_lets(s);
+
+    let Lambda::Let(..) = out.children[0].node else { panic!() };
+    let Lambda::Let(..) = out.children[0].children[1].node else { panic!() };
+    assert_eq!(count_lets(&out), 2);
+
+    assert_inlines_to(&out, s);
+}
+
+fn extract_lets(s: &str) -> RecExpr<Lambda> {
+    let mut eg: EGraph<Lambda> = EGraph::new();
+    let i = eg.add_syn_expr(RecExpr::parse(s).unwrap());
+
+    let mk_let = |x, t, b| RecExpr { node: Lambda::Let(x, AppliedId::null(), AppliedId::null()), children: vec![t, b] };
+    let mk_var = |x| RecExpr { node: Lambda::Var(x), children: vec![] };
+    DagExtractor::new(&eg, AstSize).extract_let(&i, &eg, mk_let, mk_var)
+}
+
+// `lam_normalize` doesn't know lets, so both sides are compared with their lets inlined.
+fn assert_inlines_to(out: &RecExpr<Lambda>, s: &str) {
+    let expected = inline_lets(&RecExpr::parse(s).unwrap(), &HashMap::default());
+    assert_alpha_eq(&inline_lets(out, &HashMap::default()).to_string(), &expected.to_string());
+}
+
+fn count_lets(re: &RecExpr<Lambda>) -> usize {
+    let here = matches!(re.node, Lambda::Let(..)) as usize;
+    here + re.children.iter().map(count_lets).sum::<usize>()
+}
+
+// Replaces all let-bound variables by their definition.
+fn inline_lets(re: &RecExpr<Lambda>, defs: &HashMap<Slot, RecExpr<Lambda>>) -> RecExpr<Lambda> {
+    match re.node {
+        Lambda::Let(x, _, _) => {
+            let mut defs = defs.clone();
+            defs.insert(x, inline_lets(&re.children[0], &defs));
+            inline_lets(&re.children[1], &defs)
+        },
+        Lambda::Var(x) if defs.contains_key(&x) => defs[&x].clone(),
+        _ => {
+            let children = re.children.iter().map(|c| inline_lets(c, defs)).collect();
+            RecExpr { node: re.node.clone(), children }
+        },
+    }
+}
+
+#[test]
+fn constrained_extraction() {
+    let with_let = "(let $0 (lam $1 (app (var $1) (var $1))) (app (var $0) (var $0)))";
+    let without_let = "(app (lam $1 (app (var $1) (var $1))) (lam $2 (app (var $2) (var $2))))";
+    let mut eg: EGraph<Lambda> = EGraph::new();
+    let i = eg.add_syn_expr(RecExpr::parse(with_let).unwrap());
+    let j = eg.add_syn_expr(RecExpr::parse(without_let).unwrap());
+    eg.union(&i, &j);
+
+    let constraints = Constraints::new().forbid(|x| matches!(x, Lambda::Let(..)));
+    let out = ConstrainedExtractor::new(&eg, AstSize, constraints).extract(&i, &eg).unwrap();
+    assert_alpha_eq(&out.to_string(), without_let);
+
+    // The same as with an infinite cost for lets.
+    assert_alpha_eq(&out.to_string(), &extract::<_, _, AstSizeNoLet>(&i, &eg).to_string());
+}
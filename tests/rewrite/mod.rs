@@ -0,0 +1,4 @@
+// The tests of rewrite rules, patterns and substitution methods, using the `Arith` language.
+mod rule;
+mod pattern;
+mod subst;
@@ -0,0 +1,120 @@
+use crate::*;
+
+#[test]
+fn slot_condition_roundtrip() {
+    for s in ["(lam $1 ?b\\{$1})", "(lam $1 ?b{$1, $2})", "(lam $1 ?b{$1, ..})"] {
+        let pat: Pattern<Arith> = Pattern::parse(s).unwrap();
+        assert_eq!(pat.to_string(), s);
+    }
+}
+
+#[test]
+fn slot_condition_matching() {
+    let mut eg: EGraph<Arith> = EGraph::new();
+    id("(lam $0 (add (var $0) (var $1)))", &mut eg);
+    id("(lam $0 (var $1))", &mut eg);
+
+    let count = |s: &str| ematch_all(&eg, &Pattern::parse(s).unwrap()).len();
+    assert_eq!(count("(lam $1 ?b\\{$1})"), 1);
+    assert_eq!(count("(lam $1 ?b{$1, ..})"), 1);
+    assert_eq!(count("(lam $1 ?b{$1})"), 0);
+}
+
+#[test]
+fn slot_condition_parse_errors() {
+    assert!(matches!(Pattern::<Arith>::parse("(lam $1 ?b{$1 $2})"), Err(ParseError::ExpectedRBrace(_))));
+    assert!(matches!(Pattern::<Arith>::parse("(lam $1 ?b{x})"), Err(ParseError::ExpectedSlot(_))));
+}
+
+#[test]
+fn ho_pattern_roundtrip() {
+    let pat: Pattern<Arith> = Pattern::parse("(lam $1 (app ?f[] ?b[$1, $2]))").unwrap();
+    assert_eq!(pat.to_string(), "(lam $1 (app ?f[] ?b[$1, $2]))");
+}
+
+#[test]
+fn ho_beta_var() {
+    // beta-reduction for variable arguments, by renaming.
+    let beta_var = Rewrite::<Arith>::new("beta-var", "(app (lam $1 ?b[$1]) (var $2))", "?b[$2]");
+
+    let mut eg = EGraph::new();
+    let i = id("(app (lam $0 (add (var $0) (var $3))) (var $5))", &mut eg);
+    let i2 = id("(add (var $5) (var $3))", &mut eg);
+    apply_rewrites(&mut eg, &[beta_var]);
+    assert!(eg.eq(&i, &i2));
+}
+
+#[test]
+fn ho_eta() {
+    // `?f[]` may not depend on the bound `$1`.
+    let eta = Rewrite::<Arith>::new("eta", "(lam $1 (app ?f[] (var $1)))", "?f[]");
+
+    let mut eg = EGraph::new();
+    let j = id("(lam $0 (app (var $3) (var $0)))", &mut eg);
+    let k = id("(lam $0 (app (var $0) (var $0)))", &mut eg);
+    let j2 = id("(var $3)", &mut eg);
+    let k2 = id("(var $0)", &mut eg);
+    apply_rewrites(&mut eg, &[eta]);
+    assert!(eg.eq(&j, &j2));
+    assert!(!eg.eq(&k, &k2));
+}
+
+#[test]
+fn ho_nonlinear_matching() {
+    // `?f` matches modulo renaming of its argument.
+    let mut eg: EGraph<Arith> = EGraph::new();
+    id("(lam $0 (lam $1 (add (var $0) (var $1))))", &mut eg);
+    id("(lam $0 (lam $1 (add (var $0) (var $0))))", &mut eg);
+    let pat = Pattern::parse("(lam $1 (lam $2 (add ?f[$1] ?f[$2])))").unwrap();
+    assert_eq!(ematch_all(&eg, &pat).len(), 1);
+}
+
+#[test]
+fn parse_error_payloads() {
+    // The remaining tokens are rendered, so the payloads can be used outside of the crate.
+    let Err(ParseError::RemainingRest(rest)) = RecExpr::<Arith>::parse("(add 1 2) 3") else { panic!() };
+    assert!(rest.contains('3'));
+    let Err(ParseError::ExpectedColonEquals(rest)) = Pattern::<Arith>::parse("?b[(var $1) (var $2)]") else { panic!() };
+    assert!(!rest.is_empty());
+}
+
+#[test]
+fn lhs_renaming_validation() {
+    let kind = |a: &str| Rewrite::<Arith>::try_new("r", a, "?c").err().map(|e| e.kind);
+    assert!(matches!(kind("(add ?b[(var $1) := 3] ?c)"), Some(RewriteErrorKind::UnsupportedLhs(_))));
+    // Without `\{$1}`, `?b` could be any e-class in which only some occurences of `$1` came from `$2`.
+    assert!(matches!(kind("(add ?b[(var $2) := (var $1)] ?c)"), Some(RewriteErrorKind::UnsupportedLhs(_))));
+    assert!(matches!(kind("(add ?b{$1}[(var $2) := (var $1)] ?c)"), Some(RewriteErrorKind::UnsupportedLhs(_))));
+    assert!(kind("(add ?b\\{$1}[(var $2) := (var $1)] ?c)").is_none());
+    assert!(kind("(add ?b[(var $1) := (var $1)] ?c)").is_none());
+}
+
+#[test]
+fn lhs_renaming_matching() {
+    let pat = Pattern::parse_lhs("(lam $1 (lam $2 (add ?b\\{$1}[(var $2) := (var $1)] (var $1))))").unwrap();
+
+    let mut eg: EGraph<Arith> = EGraph::new();
+    id("(lam $0 (lam $1 (add (mul (var $0) (var $3)) (var $0))))", &mut eg);
+    // `$2` is free in `(var $1)`, so it is not a renaming of anything.
+    id("(lam $0 (lam $1 (add (var $1) (var $0))))", &mut eg);
+
+    // `?b` is `(mul (var $2) (var $3))`, the only preimage that doesn't use `$1`.
+    let substs = ematch_all(&eg, &pat);
+    assert_eq!(substs.len(), 1);
+    let b = &substs[0]["b"];
+    assert_eq!(b.slots().len(), 2);
+    assert!(b.slots().contains(&Slot::named("2")));
+    assert!(!b.slots().contains(&Slot::named("1")));
+}
+
+#[test]
+fn lhs_renaming_rewrite() {
+    // swaps which binder the `add` refers to.
+    let rw = Rewrite::new("swap", "(lam $1 (lam $2 (add ?b\\{$1}[(var $2) := (var $1)] (var $1))))", "(lam $1 (lam $2 (add ?b (var $2))))");
+
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let i = id("(lam $0 (lam $1 (add (mul (var $0) (var $3)) (var $0))))", &mut eg);
+    let j = id("(lam $0 (lam $1 (add (mul (var $1) (var $3)) (var $1))))", &mut eg);
+    apply_rewrites(&mut eg, &[rw]);
+    assert!(eg.eq(&i, &j));
+}
@@ -0,0 +1,172 @@
+use crate::*;
+
+#[test]
+fn rewrite_inspection() {
+    let rw = add_comm();
+    assert_eq!(rw.name(), "add-comm");
+    assert_eq!(rw.lhs().unwrap().to_string(), "(add ?a ?b)");
+    assert_eq!(rw.rhs().unwrap().to_string(), "(add ?b ?a)");
+
+    let mut eg = EGraph::new();
+    id("(add (var $0) (add (var $1) (var $2)))", &mut eg);
+    assert_eq!(rw.search(&eg).len(), 2);
+}
+
+#[test]
+fn closure_rewrite() {
+    let pat = Pattern::parse("(add ?a ?b)").unwrap();
+    let rw: Rewrite<Arith> = Rewrite::from_parts(
+        "add-comm-closure",
+        move |eg: &EGraph<Arith>| ematch_all(eg, &pat),
+        |substs: Vec<Subst>, eg: &mut EGraph<Arith>| {
+            for subst in substs {
+                let l = eg.add(Arith::Add(subst["a"].clone(), subst["b"].clone()));
+                let r = eg.add(Arith::Add(subst["b"].clone(), subst["a"].clone()));
+                eg.union_justified(&l, &r, Some("add-comm-closure".to_string()));
+            }
+        },
+    );
+    assert!(rw.lhs().is_none());
+
+    let mut eg = EGraph::new();
+    let i = id("(add (var $0) (add (var $1) (var $2)))", &mut eg);
+    apply_rewrites(&mut eg, &[rw]);
+    let j = id("(add (add (var $2) (var $1)) (var $0))", &mut eg);
+    assert!(eg.eq(&i, &j));
+}
+
+#[test]
+fn rewrite_t_names() {
+    let unit = || RewriteT::<Arith, (), ()> { searcher: Box::new(|_| ()), applier: Box::new(|(), _| ()) };
+
+    // Without a name, every converted rule gets its own one.
+    let a: Rewrite<Arith> = unit().into();
+    let b: Rewrite<Arith> = unit().into();
+    assert!(!a.name().is_empty());
+    assert_ne!(a.name(), b.name());
+
+    let a = a.with_name("unit");
+    assert_eq!(a.name(), "unit");
+    assert!(a.lhs().is_none());
+}
+
+#[test]
+fn rewrite_t_search_apply() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // The searcher counts the e-classes, and the applier records what it got.
+    let seen: Rc<RefCell<Vec<usize>>> = Default::default();
+    let seen2 = seen.clone();
+    let rt: RewriteT<Arith, (), usize> = RewriteT {
+        searcher: Box::new(|eg| eg.ids().len()),
+        applier: Box::new(move |n, _| seen2.borrow_mut().push(n)),
+    };
+    let rw: Rewrite<Arith> = rt.into();
+
+    let mut eg = EGraph::new();
+    id("(var $0)", &mut eg);
+
+    // A search result that is never applied is just dropped.
+    let m = rw.search(&eg);
+    assert_eq!(m.len(), 1);
+    assert!(m.substs().is_empty());
+    drop(m);
+
+    id("(add (var $0) (var $0))", &mut eg);
+    let m = rw.search(&eg);
+    rw.apply(m, &mut eg);
+    assert_eq!(*seen.borrow(), vec![2]);
+}
+
+fn number(eg: &EGraph<Arith>, i: &AppliedId) -> Option<u32> {
+    eg.enodes_applied(i).into_iter().find_map(|n| match n {
+        Arith::Number(x) => Some(x),
+        _ => None,
+    })
+}
+
+fn add_fold() -> Rewrite<Arith> {
+    Rewrite::new_dyn("add-fold", "(add ?a ?b)", |subst, eg| {
+        let a = number(eg, &subst["a"])?;
+        let b = number(eg, &subst["b"])?;
+        Some(eg.add(Arith::Number(a + b)))
+    })
+}
+
+#[test]
+fn dyn_const_fold() {
+    let mut eg = EGraph::new();
+    let i = id("(add (add 2 3) 4)", &mut eg);
+    let rules = [add_fold()];
+    for _ in 0..2 {
+        apply_rewrites(&mut eg, &rules);
+    }
+    let nine = id("9", &mut eg);
+    assert!(eg.eq(&i, &nine));
+}
+
+#[test]
+fn dyn_skips_match() {
+    // The closure returns None for a non-constant operand, so nothing is unioned.
+    let mut eg = EGraph::new();
+    let j = id("(add 2 (var $0))", &mut eg);
+    apply_rewrites(&mut eg, &[add_fold()]);
+    assert_eq!(number(&eg, &j), None);
+}
+
+#[test]
+fn dyn_fresh_binder() {
+    // (add ?a ?b) => (app (lam $x (add (var $x) ?b)) ?a), for a fresh $x.
+    let abstract_lhs: Rewrite<Arith> = Rewrite::new_dyn("abstract-lhs", "(add ?a ?b)", |subst, eg| {
+        let x = Slot::fresh();
+        let var = eg.add(Arith::Var(x));
+        let body = eg.add(Arith::Add(var, subst["b"].clone()));
+        let lam = eg.add(Arith::Lam(x, body));
+        Some(eg.add(Arith::App(lam, subst["a"].clone())))
+    });
+
+    let mut eg = EGraph::new();
+    let i = id("(add (var $0) 3)", &mut eg);
+    apply_rewrites(&mut eg, &[abstract_lhs]);
+
+    let re = RecExpr::parse("(app (lam $1 (add (var $1) 3)) (var $0))").unwrap();
+    let j = lookup_rec_expr(&re, &eg).unwrap();
+    assert!(eg.eq(&i, &j));
+}
+
+fn error_kind(lhs: &str, rhs: &str) -> Option<RewriteErrorKind> {
+    Rewrite::<Arith>::try_new("r", lhs, rhs).err().map(|e| e.kind)
+}
+
+#[test]
+fn rewrite_validation() {
+    assert!(matches!(error_kind("(add ?a", "?a"), Some(RewriteErrorKind::LhsParse(_))));
+    assert!(matches!(error_kind("(add ?a ?b)", "(add ?a ?b"), Some(RewriteErrorKind::RhsParse(_))));
+    assert!(matches!(error_kind("?a", "(add ?a 0)"), Some(RewriteErrorKind::BareLhs)));
+    assert!(matches!(error_kind("(add ?a ?b)", "(add ?a ?c)"), Some(RewriteErrorKind::UnboundVar(v)) if v == "c"));
+    assert!(matches!(error_kind("(lam $1 ?b)", "(var $1)"), Some(RewriteErrorKind::FreeRhsSlot(_))));
+    assert!(error_kind("(lam $1 ?b)", "(lam $2 (add ?b (var $2)))").is_none());
+}
+
+#[test]
+fn rewrite_validation_var_kinds() {
+    // A pattern-variable needs the same slot arguments everywhere.
+    assert!(matches!(error_kind("(lam $1 ?b)", "(lam $1 ?b[$1])"), Some(RewriteErrorKind::VarKindMismatch(v)) if v == "b"));
+    assert!(matches!(error_kind("(lam $1 ?b[$1])", "(lam $1 ?b[])"), Some(RewriteErrorKind::VarKindMismatch(_))));
+    assert!(matches!(error_kind("(add ?b ?b[])", "?b"), Some(RewriteErrorKind::VarKindMismatch(_))));
+}
+
+#[test]
+fn rewrite_validation_bare_and_dyn() {
+    assert!(matches!(Rewrite::<Arith>::try_new_bare("r", "?a", "(add ?b 0)"), Err(RewriteError { kind: RewriteErrorKind::UnboundVar(_), .. })));
+    assert!(Rewrite::<Arith>::try_new_bare("r", "?a", "(add ?a 0)").is_ok());
+    assert!(matches!(Rewrite::<Arith>::try_new_dyn("r", "?a", |_, _| None), Err(RewriteError { kind: RewriteErrorKind::BareLhs, .. })));
+    assert!(matches!(Rewrite::<Arith>::try_new_dyn("r", "(add ?a", |_, _| None), Err(RewriteError { kind: RewriteErrorKind::LhsParse(_), .. })));
+}
+
+#[test]
+fn rewrite_error_display() {
+    let e = Rewrite::<Arith>::try_new("my-rule", "(add ?a ?b)", "?c").err().unwrap();
+    assert_eq!(e.to_string(), "rule `my-rule`: the pattern-variable `?c` is not bound in the lhs");
+}
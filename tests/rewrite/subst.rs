@@ -0,0 +1,208 @@
+use crate::*;
+
+fn beta() -> Rewrite<Arith> {
+    Rewrite::new("beta", "(app (lam $1 ?b) ?t)", "?b[(var $1) := ?t]")
+}
+
+#[test]
+fn egraph_subst_cycle() {
+    let mut eg: EGraph<Arith> = EGraph::with_subst_method::<EGraphSubst>();
+    let b1 = id("(add (var $1) (var $2))", &mut eg);
+    let b2 = id("(add (var $2) (var $1))", &mut eg);
+    eg.union(&b1, &b2);
+
+    // a cycle: b1 = (mul b1 1).
+    let b3 = id("(mul (add (var $1) (var $2)) 1)", &mut eg);
+    eg.union(&b1, &b3);
+
+    let i = id("(app (lam $1 (add (var $1) (var $2))) (lam $3 (var $3)))", &mut eg);
+    apply_rewrites(&mut eg, &[beta()]);
+
+    // Every e-node of the body got substituted, including the cyclic one.
+    for s in ["(add (lam $3 (var $3)) (var $2))", "(add (var $2) (lam $3 (var $3)))", "(mul (add (lam $3 (var $3)) (var $2)) 1)"] {
+        assert!(eg.eq(&i, &lookup(s, &eg).unwrap()));
+    }
+}
+
+#[test]
+fn egraph_subst_deferred_cycle() {
+    // A cycle through another class: c1 = (mul c2 1), c2 = (add c1 0).
+    let mut eg: EGraph<Arith> = EGraph::with_subst_method::<EGraphSubst>();
+    let c1 = id("(mul (var $1) (var $2))", &mut eg);
+    let c2 = id("(add (mul (var $1) (var $2)) 0)", &mut eg);
+    let c3 = id("(mul (add (mul (var $1) (var $2)) 0) 1)", &mut eg);
+    eg.union(&c1, &c3);
+    let i = id("(app (lam $1 (add (mul (var $1) (var $2)) 0)) (var $4))", &mut eg);
+    apply_rewrites(&mut eg, &[beta()]);
+    assert!(eg.eq(&i, &lookup("(add (mul (var $4) (var $2)) 0)", &eg).unwrap()));
+
+    // The deferred e-node `(mul c2 1)` of c1 got substituted as well.
+    let j = lookup("(mul (var $4) (var $2))", &eg).unwrap();
+    assert!(eg.eq(&j, &lookup("(mul (add (mul (var $4) (var $2)) 0) 1)", &eg).unwrap()));
+    assert!(!eg.eq(&c2, &i));
+}
+
+#[test]
+fn egraph_subst_avoids_capture() {
+    // The binder `$2` must not capture the free `$2` of the argument.
+    let mut eg: EGraph<Arith> = EGraph::with_subst_method::<EGraphSubst>();
+    let i = id("(app (lam $1 (lam $2 (add (var $1) (var $2)))) (var $2))", &mut eg);
+    apply_rewrites(&mut eg, &[beta()]);
+    assert!(eg.eq(&i, &lookup("(lam $3 (add (var $2) (var $3)))", &eg).unwrap()));
+}
+
+#[test]
+#[cfg(feature = "explanations")]
+fn egraph_subst_justified() {
+    // The unions made by the native substitution are justified by the rule.
+    let mut eg: EGraph<Arith> = EGraph::with_subst_method::<EGraphSubst>();
+    let b1 = id("(add (var $1) (var $2))", &mut eg);
+    let b2 = id("(mul (var $1) (var $2))", &mut eg);
+    eg.union(&b1, &b2);
+    id("(app (lam $1 (add (var $1) (var $2))) 4)", &mut eg);
+    apply_rewrites(&mut eg, &[beta()]);
+
+    let i = term("(add 4 (var $2))", &mut eg);
+    let j = term("(mul 4 (var $2))", &mut eg);
+    assert!(eg.explain_equivalence(i, j).to_string(&eg).contains("beta"));
+}
+
+fn check_simultaneous_subst<S: SubstMethod<Arith, ()>>() {
+    let mut eg: EGraph<Arith> = EGraph::with_subst_method::<S>();
+    let b = id("(add (var $1) (mul (var $2) (var $3)))", &mut eg);
+    let subst: Subst = [("b".to_string(), b)].into_iter().collect();
+
+    let pat = Pattern::parse("?b[(var $1) := (var $2), (var $2) := (add (var $1) 3)]").unwrap();
+    let i = pattern_subst(&mut eg, &pat, &subst);
+    let j = id("(add (var $2) (mul (add (var $1) 3) (var $3)))", &mut eg);
+    assert!(eg.eq(&i, &j));
+}
+
+#[test]
+fn subst_many_parse() {
+    let pat = Pattern::<Arith>::parse("?b[(var $1) := (var $2), (var $2) := (add (var $1) 3)]").unwrap();
+    assert_eq!(pat.to_string(), "?b[(var $1) := (var $2), (var $2) := (add (var $1) 3)]");
+    assert!(matches!(pat, Pattern::SubstMany(_, ref pairs) if pairs.len() == 2));
+
+    // A single pair stays a plain `Subst`.
+    assert!(matches!(Pattern::<Arith>::parse("?b[(var $1) := 3]").unwrap(), Pattern::Subst(..)));
+}
+
+#[test]
+fn simultaneous_syn_expr_subst() {
+    check_simultaneous_subst::<SynExprSubst>();
+}
+
+#[test]
+fn simultaneous_extraction_subst() {
+    check_simultaneous_subst::<ExtractionSubst>();
+}
+
+#[test]
+fn simultaneous_egraph_subst() {
+    check_simultaneous_subst::<EGraphSubst>();
+}
+
+#[test]
+fn simultaneous_default_subst_many() {
+    check_simultaneous_subst::<SequentialSubst>();
+}
+
+// Only implements `subst`, so the default `subst_many` is used.
+struct SequentialSubst;
+
+impl SubstMethod<Arith, ()> for SequentialSubst {
+    fn new_boxed() -> Box<dyn SubstMethod<Arith, ()>> {
+        Box::new(SequentialSubst)
+    }
+
+    fn subst(&mut self, b: AppliedId, x: AppliedId, t: AppliedId, eg: &mut EGraph<Arith>) -> AppliedId {
+        SynExprSubst.subst(b, x, t, eg)
+    }
+}
+
+// A lambda whose body class also contains a multiplication, applied to 4.
+// Returns how many of the two substituted body e-nodes the application is equal to.
+fn reached_bodies(rw: Rewrite<Arith>) -> usize {
+    // The e-graph uses the SynExprSubst by default.
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let b1 = id("(add (var $1) (var $2))", &mut eg);
+    let b2 = id("(mul (var $1) (var $2))", &mut eg);
+    eg.union(&b1, &b2);
+
+    let i = id("(app (lam $1 (add (var $1) (var $2))) 4)", &mut eg);
+    apply_rewrites(&mut eg, &[rw]);
+
+    ["(add 4 (var $2))", "(mul 4 (var $2))"].iter()
+        .filter(|s| lookup(s, &eg).is_some_and(|j| eg.eq(&i, &j)))
+        .count()
+}
+
+#[test]
+fn rule_default_subst_method() {
+    // The syntactic substitution only reaches one e-node of the body.
+    assert_eq!(reached_bodies(beta()), 1);
+}
+
+#[test]
+fn rule_subst_method() {
+    // The native one reaches both, although the e-graph uses the SynExprSubst.
+    assert_eq!(reached_bodies(beta().with_subst_method::<EGraphSubst>()), 2);
+}
+
+static COUNTING_INSTANCES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+// Counts its instances, to check that one instance is shared by all matches of a rule.
+struct CountingSubst;
+
+impl SubstMethod<Arith, ()> for CountingSubst {
+    fn new_boxed() -> Box<dyn SubstMethod<Arith, ()>> {
+        COUNTING_INSTANCES.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Box::new(CountingSubst)
+    }
+
+    fn subst(&mut self, b: AppliedId, x: AppliedId, t: AppliedId, eg: &mut EGraph<Arith>) -> AppliedId {
+        SynExprSubst.subst(b, x, t, eg)
+    }
+}
+
+#[test]
+fn rule_subst_method_instance() {
+    let beta = beta().with_subst_method::<CountingSubst>();
+
+    let mut eg: EGraph<Arith> = EGraph::new();
+    id("(app (lam $1 (add (var $1) 2)) 4)", &mut eg);
+    id("(app (lam $1 (mul (var $1) 3)) 5)", &mut eg);
+    apply_rewrites(&mut eg, &[beta]);
+    assert_eq!(COUNTING_INSTANCES.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[test]
+fn simultaneous_algorithm_subst() {
+    check_simultaneous_subst::<AlgorithmSubst<LocalSearchExtraction<AstSize>>>();
+}
+
+// An ExtractionAlgorithm that records how often it was used.
+#[derive(Default, Clone)]
+struct RecordingExtraction(std::rc::Rc<std::cell::Cell<usize>>);
+
+impl ExtractionAlgorithm<Arith, ()> for RecordingExtraction {
+    fn extract(&self, i: &AppliedId, eg: &EGraph<Arith>) -> RecExpr<Arith> {
+        self.0.set(self.0.get() + 1);
+        ast_size_extract(i, eg)
+    }
+}
+
+#[test]
+fn rule_subst_method_configured() {
+    let algorithm = RecordingExtraction::default();
+    let beta = beta().with_subst_method_instance(AlgorithmSubst(algorithm.clone()));
+
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let i = id("(app (lam $1 (add (var $1) 2)) 4)", &mut eg);
+    apply_rewrites(&mut eg, &[beta]);
+    assert!(eg.eq(&i, &lookup("(add 4 2)", &eg).unwrap()));
+
+    // The given instance was used, not a default one.
+    assert_eq!(algorithm.0.get(), 1);
+}
@@ -54,27 +54,23 @@ fn beta() -> Rewrite<Rise> {
 }
 
 fn eta() -> Rewrite<Rise> {
-    let pat = "(lam $1 (app ?f (var $1)))";
+    let pat = "(lam $1 (app ?f\\{$1} (var $1)))";
     let outpat = "?f";
 
-    Rewrite::new_if("eta", pat, outpat, |subst, _| {
-        !subst["f"].slots().contains(&Slot::numeric(1))
-    })
+    Rewrite::new("eta", pat, outpat)
 }
 
 fn eta_expansion() -> Rewrite<Rise> {
     let pat = "?f";
     let outpat = "(lam $1 (app ?f (var $1)))";
 
-    Rewrite::new("eta-expansion", pat, outpat)
+    Rewrite::new_bare("eta-expansion", pat, outpat)
 }
 
 fn my_let_unused() -> Rewrite<Rise> {
-    let pat = "(let $1 ?t ?b)";
+    let pat = "(let $1 ?t ?b\\{$1})";
     let outpat = "?b";
-    Rewrite::new_if("my-let-unused", pat, outpat, |subst, _| {
-        !subst["b"].slots().contains(&Slot::numeric(1))
-    })
+    Rewrite::new("my-let-unused", pat, outpat)
 }
 
 fn let_var_same() -> Rewrite<Rise> {
@@ -104,11 +100,9 @@ fn let_app_unopt() -> Rewrite<Rise> {
 }
 
 fn let_lam_diff() -> Rewrite<Rise> {
-    let pat = "(let $1 ?e (lam $2 ?body))";
+    let pat = "(let $1 ?e (lam $2 ?body{$1, ..}))";
     let outpat = "(lam $2 (let $1 ?e ?body))";
-    Rewrite::new_if("let-lam-diff", pat, outpat, |subst, _| {
-        subst["body"].slots().contains(&Slot::numeric(1))
-    })
+    Rewrite::new("let-lam-diff", pat, outpat)
 }
 
 fn let_lam_diff_unopt() -> Rewrite<Rise> {
@@ -132,7 +126,7 @@ fn let_const() -> Rewrite<Rise> {
             }
         }),
     };
-    rt.into()
+    rt.into().with_name("let-const")
 }
 
 /////////////////////
@@ -149,16 +143,14 @@ fn map_fission() -> Rewrite<Rise> {
     let mfi = 1;
 
     let pat = &format!(
-        "(app map (lam ${x} (app ?f ?gx)))"
+        "(app map (lam ${x} (app ?f\\{{${x}}} ?gx)))"
     );
 
     let outpat = &format!(
         "(lam ${mfi} (app (app map ?f) (app (app map (lam ${x} ?gx)) (var ${mfi}))))"
     );
 
-    Rewrite::new_if("map-fission", pat, outpat, move |subst, _| {
-        !subst["f"].slots().contains(&Slot::numeric(x))
-    })
+    Rewrite::new("map-fission", pat, outpat)
 }
 
 fn remove_transpose_pair() -> Rewrite<Rise> {
@@ -247,7 +239,7 @@ fn beta_extr() -> Rewrite<Rise> {
             }
         }),
     };
-    rt.into()
+    rt.into().with_name("beta-extr")
 }
 
 // why is this faster than beta_extr?
@@ -278,7 +270,7 @@ fn beta_extr_direct() -> Rewrite<Rise> {
             }
         }),
     };
-    rt.into()
+    rt.into().with_name("beta-extr-direct")
 }
 
 fn re_subst(s: Slot, b: RecExpr<Rise>, t: &RecExpr<Rise>) -> RecExpr<Rise> {
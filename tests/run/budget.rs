@@ -0,0 +1,89 @@
+use crate::*;
+
+fn chain() -> RecExpr<Arith> {
+    RecExpr::parse(&add_chain(0..8)).unwrap()
+}
+
+// These rules don't saturate the chain in a reasonable time.
+fn explosive() -> [Rewrite<Arith>; 2] {
+    [add_comm(), Rewrite::new("add-assoc", "(add ?a (add ?b ?c))", "(add (add ?a ?b) ?c)")]
+}
+
+#[test]
+fn cancelled_before_run() {
+    let token = CancellationToken::new();
+    token.cancel();
+    let runner: Runner<Arith> = Runner::new().with_expr(&chain()).with_cancellation_token(token).run(&explosive());
+    assert_eq!(runner.stop_reason, Some(StopReason::Cancelled));
+    assert!(runner.iterations.is_empty());
+}
+
+#[test]
+fn cancelled_by_rule() {
+    // Cancelled by the first rule, so the second rule is never applied.
+    let token = CancellationToken::new();
+    let t = token.clone();
+    let rules = [
+        Rewrite::new_dyn("cancel", "(add ?a ?b)", move |_, _| { t.cancel(); None }),
+        add_comm(),
+    ];
+    let runner: Runner<Arith> = Runner::new().with_expr(&chain()).with_cancellation_token(token).run(&rules);
+    assert_eq!(runner.stop_reason, Some(StopReason::Cancelled));
+    assert_eq!(runner.iterations.len(), 1);
+    assert_eq!(runner.report().rule_totals()[1].unions, 0);
+}
+
+#[test]
+fn cancelled_by_hook() {
+    // Cancelled between two iterations of a run that would otherwise go on for a long time.
+    let token = CancellationToken::new();
+    let t = token.clone();
+    let runner: Runner<Arith> = Runner::new()
+        .with_expr(&chain())
+        .with_iter_limit(usize::MAX)
+        .with_node_limit(usize::MAX)
+        .with_time_limit(std::time::Duration::from_secs(600))
+        .with_cancellation_token(token)
+        .with_hook(move |_| { t.cancel(); Ok(()) })
+        .run(&explosive());
+    assert_eq!(runner.stop_reason, Some(StopReason::Cancelled));
+    assert_eq!(runner.iterations.len(), 1);
+    runner.egraph.check();
+}
+
+#[test]
+fn memory_limit() {
+    // The memory limit is checked within an iteration.
+    let calls = std::sync::atomic::AtomicUsize::new(0);
+    let runner: Runner<Arith> = Runner::new()
+        .with_expr(&chain())
+        .with_memory_limit(10, move || calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed))
+        .run(&explosive());
+    assert_eq!(runner.stop_reason, Some(StopReason::MemoryLimit));
+    assert_eq!(runner.iterations.len(), 1);
+    runner.egraph.check();
+}
+
+#[test]
+fn egraph_budget_respected() {
+    // The budget of the e-graph is respected during the run, and restored after it.
+    let token = CancellationToken::new();
+    token.cancel();
+    let mut eg: EGraph<Arith> = EGraph::new();
+    eg.add_syn_expr(chain());
+    eg.set_budget(Budget::new().with_cancellation_token(token));
+    let runner: Runner<Arith> = Runner::new().with_egraph(eg).with_iter_limit(1).run(&explosive());
+    assert_eq!(runner.stop_reason, Some(StopReason::Cancelled));
+    assert!(runner.egraph.check_budget());
+}
+
+#[test]
+fn smaller_node_limit() {
+    // The smaller node limit wins.
+    let mut eg: EGraph<Arith> = EGraph::new();
+    eg.add_syn_expr(chain());
+    eg.set_budget(Budget::new().with_node_limit(1));
+    let runner: Runner<Arith> = Runner::new().with_egraph(eg).run(&explosive());
+    assert_eq!(runner.stop_reason, Some(StopReason::NodeLimit));
+    assert!(runner.iterations.is_empty());
+}
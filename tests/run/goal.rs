@@ -0,0 +1,96 @@
+use crate::*;
+
+fn rules() -> Vec<Rewrite<Arith>> {
+    vec![
+        add_comm(),
+        Rewrite::new("mul-comm", "(mul ?a ?b)", "(mul ?b ?a)"),
+        Rewrite::new("distr2", "(add (mul ?a ?b) (mul ?a ?c))", "(mul ?a (add ?b ?c))"),
+    ]
+}
+
+// (x*y) + (x*z), which factors to a term of size 5.
+fn distributed() -> RecExpr<Arith> {
+    RecExpr::parse("(add (mul (var $0) (var $1)) (mul (var $0) (var $2)))").unwrap()
+}
+
+#[test]
+fn run_until_equal() {
+    let a = RecExpr::parse("(add (var $0) (mul (var $1) (var $2)))").unwrap();
+    let b = RecExpr::parse("(add (mul (var $2) (var $1)) (var $0))").unwrap();
+    let runner: Runner<Arith> = Runner::new().run_until_equal(&a, &b, &rules());
+    assert_eq!(runner.stop_reason, Some(StopReason::GoalReached));
+    assert!(runner.egraph.eq(&runner.roots[0], &runner.roots[1]));
+    #[cfg(feature = "explanations")]
+    assert!(runner.explanation.is_some());
+}
+
+#[test]
+fn goal_reached_initially() {
+    // The goal is already reached before the first iteration.
+    let a = RecExpr::parse("(add (var $0) (mul (var $1) (var $2)))").unwrap();
+    let runner: Runner<Arith> = Runner::new().run_until_equal(&a, &a, &rules());
+    assert_eq!(runner.stop_reason, Some(StopReason::GoalReached));
+    assert!(runner.iterations.is_empty());
+}
+
+#[test]
+fn pattern_goal() {
+    let c = distributed();
+    let pat = Pattern::parse("(mul ?a (add ?b ?c))").unwrap();
+    let runner: Runner<Arith> = Runner::new()
+        .with_expr(&c)
+        .with_goal(PatternGoal::new(&c, &pat))
+        .run(&rules());
+    assert_eq!(runner.stop_reason, Some(StopReason::GoalReached));
+    assert_eq!(runner.iterations.len(), 1);
+}
+
+#[test]
+fn cost_goal() {
+    let c = distributed();
+    let runner: Runner<Arith> = Runner::new()
+        .with_expr(&c)
+        .with_goal(CostGoal::new(&c, AstSize, 5))
+        .run(&rules());
+    assert_eq!(runner.stop_reason, Some(StopReason::GoalReached));
+}
+
+#[test]
+fn unreached_goal() {
+    // A goal that is never reached doesn't prevent saturation.
+    let c = distributed();
+    let runner: Runner<Arith> = Runner::new()
+        .with_expr(&c)
+        .with_goal(CostGoal::new(&c, AstSize, 4))
+        .run(&rules());
+    assert_eq!(runner.stop_reason, Some(StopReason::Saturated));
+}
+
+fn check_pattern_matches_app(pat: &str) {
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let i = id("(app (var $0) (lam $1 (var $1)))", &mut eg);
+    let pat = Pattern::parse(pat).unwrap();
+    let substs = ematch_class(&eg, &pat, &i);
+    assert_eq!(substs.len(), 1);
+    assert_eq!(pattern_subst(&mut eg, &pat, &substs[0]), i);
+}
+
+#[test]
+fn pattern_fresh_slot_name() {
+    check_pattern_matches_app("(app ?a (lam $5 (var $5)))");
+}
+
+#[test]
+fn pattern_slot_name_of_free_slot() {
+    // The pattern may bind a slot with the same name as a free slot of the term.
+    check_pattern_matches_app("(app ?a (lam $0 (var $0)))");
+}
+
+#[test]
+fn pattern_bound_and_free_slot() {
+    // But `?b` can't use both the bound `$0` and the free `$0`.
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let i = id("(lam $1 (add (var $0) (var $1)))", &mut eg);
+    assert_eq!(ematch_class(&eg, &Pattern::parse("(lam $2 ?b)").unwrap(), &i).len(), 1);
+    assert!(ematch_class(&eg, &Pattern::parse("(lam $0 ?b)").unwrap(), &i).is_empty());
+}
@@ -0,0 +1,6 @@
+// The tests of the schedulers, the runner and its reports, using the `Arith` language.
+mod scheduler;
+mod runner;
+mod goal;
+mod report;
+mod budget;
@@ -0,0 +1,68 @@
+use crate::*;
+
+// The report of saturating a term with add-comm and mul-comm.
+fn comm_report() -> Report {
+    let re = RecExpr::parse("(add (var $0) (mul (var $1) (var $2)))").unwrap();
+    let rules = [add_comm(), Rewrite::new("mul-comm", "(mul ?a ?b)", "(mul ?b ?a)")];
+    let runner: Runner<Arith> = Runner::new().with_expr(&re).run(&rules);
+    runner.report()
+}
+
+#[test]
+fn report_rule_totals() {
+    let report = comm_report();
+    assert_eq!(report.stop_reason, StopReason::Saturated);
+
+    let totals = report.rule_totals();
+    assert_eq!(totals.len(), 2);
+    assert_eq!(totals[0].name, "add-comm");
+    assert_eq!(totals[1].name, "mul-comm");
+    // Each rule makes exactly one new equation, the further matches are already known.
+    assert_eq!(totals[0].unions, 1);
+    assert_eq!(totals[1].unions, 1);
+    assert!(totals[0].matches > 1);
+}
+
+#[test]
+fn report_iteration() {
+    let report = comm_report();
+    let it = &report.iterations[0];
+    assert!(it.changed);
+    assert_eq!(it.rules.len(), 2);
+    assert_eq!(it.progress.number_of_live_classes, it.egraph_classes);
+}
+
+#[test]
+fn report_csv() {
+    let report = comm_report();
+    let csv = report.to_csv();
+    assert!(csv.starts_with("iteration,rule,matches,unions,"));
+    assert_eq!(csv.lines().count(), 1 + 2 * report.iterations.len());
+}
+
+#[test]
+fn report_json() {
+    let json = comm_report().to_json();
+    assert!(json.starts_with("{\"stop_reason\":\"Saturated\","));
+    assert!(json.contains("\"name\":\"mul-comm\""));
+}
+
+#[test]
+fn report_display() {
+    let s = comm_report().to_string();
+    assert!(s.contains("Stop reason: Saturated"));
+    assert!(s.contains("add-comm: "));
+}
+
+#[test]
+fn report_subst_unions() {
+    // The union made by the EGraphSubst inside the rhs doesn't count for the rule.
+    let mut eg: EGraph<Arith> = EGraph::with_subst_method::<EGraphSubst>();
+    let b1 = id("(add (var $1) (var $2))", &mut eg);
+    let b2 = id("(mul (var $1) (var $2))", &mut eg);
+    eg.union(&b1, &b2);
+    id("(app (lam $1 (add (var $1) (var $2))) 4)", &mut eg);
+    let beta = Rewrite::new("beta", "(app (lam $1 ?b) ?t)", "?b[(var $1) := ?t]");
+    let runner: Runner<Arith> = Runner::new().with_egraph(eg).with_iter_limit(1).run(&[beta]);
+    assert_eq!(runner.report().rule_totals()[0].unions, 1);
+}
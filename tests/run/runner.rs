@@ -0,0 +1,94 @@
+use crate::*;
+
+fn start() -> RecExpr<Arith> {
+    RecExpr::parse("(add (var $0) (mul (var $1) (var $2)))").unwrap()
+}
+
+fn goal() -> RecExpr<Arith> {
+    RecExpr::parse("(add (mul (var $2) (var $1)) (var $0))").unwrap()
+}
+
+fn rules() -> Vec<Rewrite<Arith>> {
+    vec![add_comm(), Rewrite::new("mul-comm", "(mul ?a ?b)", "(mul ?b ?a)")]
+}
+
+#[test]
+fn runner_saturates() {
+    let runner: Runner<Arith> = Runner::new().with_expr(&start()).run(&rules());
+    assert_eq!(runner.stop_reason, Some(StopReason::Saturated));
+    assert_eq!(runner.roots.len(), 1);
+    let j = lookup_rec_expr(&goal(), &runner.egraph).unwrap();
+    assert!(runner.egraph.eq(&runner.roots[0], &j));
+    assert!(!runner.iterations.last().unwrap().changed);
+}
+
+#[test]
+fn runner_hook_calls() {
+    // The hook is called once per iteration.
+    let mut hook_calls = 0;
+    let runner: Runner<Arith> = Runner::new()
+        .with_expr(&start())
+        .with_hook(|_| { hook_calls += 1; Ok(()) })
+        .run(&rules());
+    let n = runner.iterations.len();
+    drop(runner);
+    assert_eq!(hook_calls, n);
+}
+
+#[test]
+fn runner_with_egraph() {
+    // The roots of the replaced e-graph are dropped.
+    let runner: Runner<Arith> = Runner::new().with_expr(&start()).with_egraph(EGraph::new());
+    assert!(runner.roots.is_empty());
+}
+
+#[test]
+#[allow(deprecated)]
+fn run_eqsat_saturates() {
+    // The deprecated `run_eqsat` still works on the given e-graph.
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let i = eg.add_syn_expr(start());
+    let report = run_eqsat(&mut eg, rules(), 30, 5, |_| Ok(()));
+    assert_eq!(report.stop_reason, StopReason::Saturated);
+    assert!(eg.eq(&i, &lookup_rec_expr(&goal(), &eg).unwrap()));
+}
+
+#[test]
+#[allow(deprecated)]
+fn run_eqsat_iter_limit() {
+    // As before, it applies `iter_limit + 1` rounds of rewrites.
+    let mut eg: EGraph<Arith> = EGraph::new();
+    eg.add_syn_expr(start());
+    let report = run_eqsat(&mut eg, rules(), 0, 5, |_| Ok(()));
+    assert_eq!(report.stop_reason, StopReason::IterationLimit);
+    assert_eq!(report.iterations.len(), 1);
+}
+
+#[test]
+fn runner_iter_limit() {
+    let runner: Runner<Arith> = Runner::new().with_expr(&start()).with_iter_limit(1).run(&rules());
+    assert_eq!(runner.stop_reason, Some(StopReason::IterationLimit));
+    assert_eq!(runner.report().iterations.len(), 1);
+}
+
+#[test]
+fn runner_node_limit() {
+    let runner: Runner<Arith> = Runner::new().with_expr(&start()).with_node_limit(1).run(&rules());
+    assert_eq!(runner.stop_reason, Some(StopReason::NodeLimit));
+}
+
+#[test]
+fn runner_time_limit() {
+    let runner: Runner<Arith> = Runner::new().with_expr(&start()).with_time_limit(std::time::Duration::ZERO).run(&rules());
+    assert_eq!(runner.stop_reason, Some(StopReason::TimeLimit));
+}
+
+#[test]
+fn runner_hook_error() {
+    let runner: Runner<Arith> = Runner::new()
+        .with_expr(&start())
+        .with_hook(|_| Err("stop".to_string()))
+        .run(&rules());
+    assert_eq!(runner.stop_reason, Some(StopReason::Other("stop".to_string())));
+    assert_eq!(runner.iterations.len(), 1);
+}
@@ -0,0 +1,72 @@
+use crate::*;
+
+// Two matches of add-comm.
+fn two_adds() -> EGraph<Arith> {
+    let mut eg: EGraph<Arith> = EGraph::new();
+    id("(add (add (var $0) (var $1)) (var $2))", &mut eg);
+    eg
+}
+
+#[test]
+fn backoff_ban() {
+    let eg = two_adds();
+    let rw = add_comm();
+    assert_eq!(rw.search(&eg).len(), 2);
+
+    let mut sched = BackoffScheduler::default().with_initial_match_limit(1).with_ban_length(2);
+    // 2 > 1 matches, so the rule is banned for the iterations 0 and 1.
+    assert!(sched.search_rewrite(0, &eg, 0, &rw).is_empty());
+    assert!(sched.search_rewrite(1, &eg, 0, &rw).is_empty());
+    // Now the match limit is doubled.
+    assert_eq!(sched.search_rewrite(2, &eg, 0, &rw).len(), 2);
+}
+
+#[test]
+fn backoff_same_name() {
+    // Rules with the same name are banned independently.
+    let mut eg = two_adds();
+    let rw = add_comm();
+    let same_name = Rewrite::new("add-comm", "(mul ?a ?b)", "(mul ?b ?a)");
+    let mut sched = BackoffScheduler::default().with_initial_match_limit(1);
+    assert!(sched.search_rewrite(0, &eg, 0, &rw).is_empty());
+    assert!(sched.search_rewrite(0, &eg, 1, &same_name).is_empty());
+    id("(mul (var $0) (var $1))", &mut eg);
+    assert_eq!(sched.search_rewrite(1, &eg, 1, &same_name).len(), 1);
+}
+
+#[test]
+fn backoff_can_stop() {
+    // After a ban, the scheduler doesn't allow stopping, but unbans the rules instead.
+    let mut eg = two_adds();
+    let mut sched = BackoffScheduler::default().with_initial_match_limit(1);
+    assert!(!apply_rewrites_with(&mut eg, &[add_comm()], &mut sched, 0));
+    assert!(!Scheduler::<Arith, ()>::can_stop(&mut sched, 0));
+    assert!(Scheduler::<Arith, ()>::can_stop(&mut sched, 0));
+}
+
+#[test]
+fn backoff_do_not_ban() {
+    let mut eg = two_adds();
+    let mut sched = BackoffScheduler::default().with_initial_match_limit(1).do_not_ban("add-comm");
+    assert!(apply_rewrites_with(&mut eg, &[add_comm()], &mut sched, 0));
+}
+
+#[test]
+fn rule_priorities() {
+    let mut eg: EGraph<Arith> = EGraph::new();
+    let i = id("(add (var $0) (mul (var $1) (var $2)))", &mut eg);
+    let rules = [
+        Rewrite::new("mul-comm", "(mul ?a ?b)", "(mul ?b ?a)").with_priority(1),
+        add_comm(),
+    ];
+
+    // The first iteration only applies the higher-priority rule.
+    assert!(apply_rewrites_with(&mut eg, &rules, &mut SimpleScheduler, 0));
+    assert!(lookup("(add (var $0) (mul (var $2) (var $1)))", &eg).is_some());
+    assert!(lookup("(add (mul (var $1) (var $2)) (var $0))", &eg).is_none());
+
+    // Afterwards, mul-comm doesn't change anything anymore, so add-comm is applied.
+    assert!(apply_rewrites_with(&mut eg, &rules, &mut SimpleScheduler, 1));
+    assert!(eg.eq(&i, &lookup("(add (mul (var $1) (var $2)) (var $0))", &eg).unwrap()));
+    assert!(!apply_rewrites_with(&mut eg, &rules, &mut SimpleScheduler, 2));
+}
@@ -1,13 +1,10 @@
 use crate::*;
 
 pub fn sdql_rules() -> Vec<Rewrite<Sdql>> {
-    let pat = "(sum $x $y ?R (sing ?e1 ?e2))";
+    let pat = "(sum $x $y ?R (sing ?e1\\{$x, $y} ?e2))";
     let outpat = "(sing ?e1 (sum $x $y ?R ?e2))";
 
-    vec![Rewrite::new_if("rule1", pat, outpat, |subst, _| {
-        !subst["e1"].slots().contains(&Slot::named("x"))
-        && !subst["e1"].slots().contains(&Slot::named("y"))
-    })]
+    vec![Rewrite::new("rule1", pat, outpat)]
 
     //rw!("sum-fact-3";  "(sum ?R (sing ?e1 ?e2))"        => 
     //        { with_shifted_double_down(var("?e1"), var("?e1d"), 2, "(sing ?e1d (sum ?R ?e2))".parse::<Pattern<SDQL>>().unwrap()) }
@@ -30,3 +27,19 @@ fn t1() {
     eprintln!("{}", re.to_string());
     eprintln!("{}", term.to_string());
 }
+
+#[test]
+fn sum_sing() {
+    // Summing over a singleton dictionary binds both the key and the value.
+    let rule: Rewrite<Sdql> = Rewrite::new("sum-sing", "(sum $k $v (sing ?k1 ?v1) ?body)", "?body[(var $k) := ?k1, (var $v) := ?v1]");
+
+    let re: RecExpr<Sdql> = RecExpr::parse("(lambda $a (lambda $b (sum $i $j (sing (var $a) (var $b)) (sing (var $j) (var $i)))))").unwrap();
+    let goal: RecExpr<Sdql> = RecExpr::parse("(lambda $a (lambda $b (sing (var $b) (var $a))))").unwrap();
+
+    let mut eg = EGraph::new();
+    let i = eg.add_syn_expr(re);
+    apply_rewrites(&mut eg, &[rule]);
+
+    let j = lookup_rec_expr(&goal, &eg).unwrap();
+    assert!(eg.eq(&i, &j));
+}